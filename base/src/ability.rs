@@ -0,0 +1,196 @@
+use derive_more::Display;
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+use crate::game::{DamageKind, Origin};
+use crate::persist::{PersistExtension, PersistModule, PersistTagExtension};
+use crate::status::StatusKind;
+use crate::util::flecs_extension::KfWorldExtensions;
+use crate::util::pos::Pos;
+
+/// Which units an ability affects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeJson, SerJson)]
+pub enum TargetShape {
+    /// only the caster, no target needed
+    Caster,
+    /// the unit standing on the target tile
+    Single,
+    /// every unit within the radius around the target tile
+    Ball,
+}
+
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub enum AbilityEffect {
+    Damage {
+        kind: DamageKind,
+        amount: i32,
+    },
+    Heal {
+        amount: i32,
+    },
+    /// away from the caster, or from the center for balls
+    Push {
+        distance: i32,
+    },
+    Status {
+        status: StatusKind,
+        turns: i32,
+    },
+}
+
+/// Lives on its own entity, units own it through (HasAbility, ability)
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+pub struct Ability {
+    pub name: String,
+    pub shape: TargetShape,
+    /// in tiles, ignored for TargetShape::Caster
+    pub range: i32,
+    /// only used by TargetShape::Ball
+    pub radius: i32,
+    /// in mana
+    pub cost: i32,
+    /// turns until it can be cast again
+    pub cooldown: i32,
+    pub effects: Vec<AbilityEffect>,
+}
+
+impl Ability {
+    /// Whether the AI should aim this at its enemies
+    pub fn is_offensive(&self) -> bool {
+        self.shape != TargetShape::Caster
+            && self.effects.iter().any(|effect| {
+                matches!(
+                    effect,
+                    AbilityEffect::Damage { .. }
+                        | AbilityEffect::Push { .. }
+                        | AbilityEffect::Status { .. }
+                )
+            })
+    }
+}
+
+/// On the ability entity while it recharges
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Cooldown {
+    pub turns: i32,
+}
+
+/// Spent on abilities, refills one point every `turns_per_point` turns
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Mana {
+    pub max: i32,
+    pub current: i32,
+    pub turns_per_point: i32,
+    pub progress: i32,
+}
+
+#[derive(Component)]
+#[meta]
+/// Relation
+/// (HasAbility, Ability)
+pub struct HasAbility {}
+
+/// Gives the unit its own copy of the ability, returns the ability entity
+pub fn grant_ability(unit: EntityView, ability: Ability) -> Entity {
+    let e = unit.world().entity().set(ability);
+    unit.add_first::<HasAbility>(e);
+    *e
+}
+
+/// The ability entities the unit owns
+pub fn abilities(unit: EntityView) -> Vec<Entity> {
+    let mut result = Vec::new();
+    unit.each_target::<HasAbility>(|ability| result.push(*ability));
+    result
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum CastError {
+    #[display(fmt = "That needs {} more turns to recharge.", _0)]
+    Cooldown(i32),
+    #[display(fmt = "You do not have enough mana.")]
+    NotEnoughMana,
+    #[display(fmt = "That is out of range.")]
+    OutOfRange,
+}
+
+/// Checks cooldown and mana, but not the target
+pub fn ability_ready(caster: EntityView, ability: EntityView) -> Result<(), CastError> {
+    if let Some(turns) = ability.try_get::<&Cooldown>(|cd| cd.turns) {
+        return Err(CastError::Cooldown(turns));
+    }
+    let cost = ability.get::<&Ability>(|a| a.cost);
+    let mana = caster.try_get::<&Mana>(|m| m.current).unwrap_or(0);
+    if cost > mana {
+        return Err(CastError::NotEnoughMana);
+    }
+    Ok(())
+}
+
+/// Pays for the ability and creates its CastEvent.
+/// Checking the line of fire is up to the caller.
+/// Shared by the player and the AI.
+pub fn cast(caster: EntityView, ability: EntityView, target: Pos) -> Result<(), CastError> {
+    ability_ready(caster, ability)?;
+    let data = ability.get::<&Ability>(|a| a.clone());
+    let caster_pos = caster.get::<&Pos>(|pos| *pos);
+    let target = match data.shape {
+        TargetShape::Caster => caster_pos,
+        TargetShape::Single | TargetShape::Ball => {
+            if caster_pos.distance(target) > data.range {
+                return Err(CastError::OutOfRange);
+            }
+            target
+        }
+    };
+
+    if data.cost > 0 {
+        caster.get::<&mut Mana>(|m| m.current -= data.cost);
+    }
+    if data.cooldown > 0 {
+        ability.set(Cooldown {
+            turns: data.cooldown,
+        });
+    }
+    CastEvent::create(&caster.world(), data, target, *caster);
+    Ok(())
+}
+
+/// Gets resolved into DamageEvent, HealEvent, PushEvent and StatusEvent
+/// for every unit the ability reaches
+#[derive(Component)]
+pub struct CastEvent {
+    pub ability: Ability,
+    pub target: Pos,
+}
+
+impl CastEvent {
+    pub fn create<'a>(
+        world: &'a World,
+        ability: Ability,
+        target: Pos,
+        origin: Entity,
+    ) -> EntityView<'a> {
+        world
+            .entity()
+            .set(Self { ability, target })
+            .add_first::<Origin>(origin)
+    }
+}
+
+#[derive(Component)]
+pub struct AbilityComponents {}
+
+impl Module for AbilityComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<CastEvent>();
+        world.component_kf::<Ability>().persist();
+        world.component_kf::<Cooldown>().meta().persist();
+        world.component_kf::<Mana>().meta().persist();
+        world.component_kf::<HasAbility>().meta().persist();
+    }
+}
@@ -0,0 +1,182 @@
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+use crate::equipment::{ranged_weapon, Weapon};
+use crate::game::{DamageEvent, Origin, PushEvent, Target};
+use crate::hunger::HungerState;
+use crate::persist::{PersistExtension, PersistModule};
+use crate::status::StatusEvent;
+use crate::util::flecs_extension::KfWorldExtensions;
+use crate::util::pos::Pos;
+use crate::util::rng::Rng;
+
+/// Percent values, compared against a d100.
+/// Units without this component use the defaults.
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct CombatStats {
+    pub accuracy: i32,
+    pub evasion: i32,
+    pub crit_chance: i32,
+}
+
+impl Default for CombatStats {
+    fn default() -> Self {
+        Self {
+            accuracy: 75,
+            evasion: 0,
+            crit_chance: 5,
+        }
+    }
+}
+
+impl CombatStats {
+    /// Includes the penalties of hunger
+    pub fn of(e: EntityView) -> Self {
+        let mut stats = e.try_get::<&CombatStats>(|s| s.clone()).unwrap_or_default();
+        let hunger = HungerState::of(e);
+        stats.accuracy -= hunger.accuracy_penalty();
+        stats.evasion -= hunger.evasion_penalty();
+        stats
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttackRoll {
+    Miss,
+    Hit { damage: i32, critical: bool },
+}
+
+/// Even the best fighter misses sometimes and the clumsiest gets lucky.
+pub const MIN_HIT_CHANCE: i32 = 5;
+pub const MAX_HIT_CHANCE: i32 = 95;
+
+pub fn roll_attack(
+    rng: &mut Rng,
+    attacker: &CombatStats,
+    defender: &CombatStats,
+    weapon: &Weapon,
+) -> AttackRoll {
+    let hit_chance = (attacker.accuracy - defender.evasion).clamp(MIN_HIT_CHANCE, MAX_HIT_CHANCE);
+    if !rng.chance(hit_chance) {
+        return AttackRoll::Miss;
+    }
+    let damage = rng.range(weapon.min_damage, weapon.max_damage);
+    let critical = rng.chance(attacker.crit_chance);
+    let damage = if critical { damage * 2 } else { damage };
+    AttackRoll::Hit { damage, critical }
+}
+
+#[derive(Component)]
+#[meta]
+/// Gets resolved into DamageEvent, PushEvent and StatusEvent if it hits
+pub struct AttackEvent {}
+
+impl AttackEvent {
+    pub fn create<'a>(world: &'a World, origin: Entity, targets: &[Entity]) -> EntityView<'a> {
+        let ev = world
+            .entity()
+            .add::<AttackEvent>()
+            .add_first::<Origin>(origin);
+        for target in targets {
+            ev.add_first::<Target>(*target);
+        }
+        ev
+    }
+}
+
+/// Attacks the target with whatever the attacker has equipped.
+/// Shared by the player and the AI.
+pub fn melee_attack(attacker: EntityView, target: EntityView) {
+    AttackEvent::create(&attacker.world(), *attacker, &[*target]);
+}
+
+/// Creates the events for an attack that connected.
+pub fn apply_hit(attacker: EntityView, target: EntityView, weapon: &Weapon, damage: i32) {
+    let world = attacker.world();
+    DamageEvent::create(&world, weapon.kind, damage, *attacker, &[*target]);
+    // the environment has no position to push away from
+    let a_pos = attacker.try_get::<&Pos>(|pos| *pos);
+    if let (true, Some(a_pos)) = (weapon.push > 0, a_pos) {
+        let t_pos = target.get::<&Pos>(|pos| *pos);
+        PushEvent::create(&world, t_pos - a_pos, weapon.push, *attacker, &[*target]);
+    }
+    for effect in &weapon.on_hit {
+        StatusEvent::create(&world, effect.status, effect.turns, *attacker, &[*target]);
+    }
+}
+
+/// Flies along its path a few tiles per turn and attacks the first unit in the way.
+/// Has an (Origin, shooter) pair so hits are credited to whoever fired it.
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+pub struct Projectile {
+    pub path: Vec<Pos>,
+    /// index into path of the next tile to enter
+    pub next: usize,
+    pub speed: i32,
+    pub weapon: Weapon,
+}
+
+/// Fires whatever the shooter has in the ranged slot at the target position.
+/// Checking the line of fire is up to the caller.
+/// Shared by the player and the AI.
+pub fn fire_projectile(shooter: EntityView, target: Pos) -> Option<Entity> {
+    let (weapon, ranged) = ranged_weapon(shooter)?;
+    let start = shooter.try_get::<&Pos>(|pos| *pos)?;
+    let path = start.line_to(target);
+    if path.is_empty() || start.distance(target) > ranged.range {
+        return None;
+    }
+    let projectile = shooter
+        .world()
+        .entity()
+        .set(Projectile {
+            path,
+            next: 0,
+            speed: ranged.speed,
+            weapon,
+        })
+        .set(start)
+        .add_first::<Origin>(shooter);
+    Some(*projectile)
+}
+
+#[derive(Component)]
+pub struct CombatComponents {}
+
+impl Module for CombatComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<AttackEvent>().meta();
+        world.component_kf::<CombatStats>().meta().persist();
+        world.component_kf::<Projectile>().persist();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::DamageKind;
+
+    use super::*;
+
+    #[test]
+    fn roll_attack_is_reproducible() {
+        let weapon = Weapon {
+            kind: DamageKind::Cutting,
+            min_damage: 1,
+            max_damage: 6,
+            push: 0,
+            on_hit: vec![],
+        };
+        let stats = CombatStats::default();
+        let mut a = Rng::new(99);
+        let mut b = Rng::new(99);
+        for _ in 0..50 {
+            assert_eq!(
+                roll_attack(&mut a, &stats, &stats, &weapon),
+                roll_attack(&mut b, &stats, &stats, &weapon)
+            );
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+use crate::game::DamageKind;
+use crate::persist::{PersistExtension, PersistModule, PersistTagExtension};
+use crate::status::OnHit;
+use crate::util::flecs_extension::KfWorldExtensions;
+
+#[derive(Component, Debug, DeJson, SerJson)]
+#[meta]
+pub struct Item {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeJson, SerJson)]
+pub enum EquipSlot {
+    Weapon,
+    Armor,
+    Shield,
+    Ring,
+    Ranged,
+}
+
+impl EquipSlot {
+    pub const ALL: [EquipSlot; 5] = [
+        EquipSlot::Weapon,
+        EquipSlot::Armor,
+        EquipSlot::Shield,
+        EquipSlot::Ring,
+        EquipSlot::Ranged,
+    ];
+
+    /// The item the unit currently has in this slot
+    pub fn item<'a>(self, unit: EntityView<'a>) -> Option<EntityView<'a>> {
+        match self {
+            EquipSlot::Weapon => unit.target::<WeaponSlot>(0),
+            EquipSlot::Armor => unit.target::<ArmorSlot>(0),
+            EquipSlot::Shield => unit.target::<ShieldSlot>(0),
+            EquipSlot::Ring => unit.target::<RingSlot>(0),
+            EquipSlot::Ranged => unit.target::<RangedSlot>(0),
+        }
+    }
+}
+
+#[derive(Component, Debug, DeJson, SerJson)]
+pub struct Equippable {
+    pub slot: EquipSlot,
+}
+
+/// What a unit hits with when it bumps into something.
+/// Everything besides the damage itself is an on hit effect.
+/// Sits on items, or on the unit itself for natural attacks like bites.
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+pub struct Weapon {
+    pub kind: DamageKind,
+    pub min_damage: i32,
+    pub max_damage: i32,
+    pub push: i32,
+    pub on_hit: Vec<OnHit>,
+}
+
+/// Used when nothing is in the weapon slot.
+pub const UNARMED: Weapon = Weapon {
+    kind: DamageKind::Blunt,
+    min_damage: 1,
+    max_damage: 1,
+    push: 0,
+    on_hit: Vec::new(),
+};
+
+/// Lets a weapon in the ranged slot shoot projectiles.
+/// The damage comes from its Weapon component.
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Ranged {
+    /// in tiles
+    pub range: i32,
+    /// tiles the projectile flies per turn
+    pub speed: i32,
+}
+
+/// Reduces physical damage taken while equipped.
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Armor {
+    pub reduction: i32,
+}
+
+#[derive(Component)]
+#[meta]
+/// Relation, exclusive
+/// (WeaponSlot, Item)
+pub struct WeaponSlot {}
+
+#[derive(Component)]
+#[meta]
+/// Relation, exclusive
+/// (ArmorSlot, Item)
+pub struct ArmorSlot {}
+
+#[derive(Component)]
+#[meta]
+/// Relation, exclusive
+/// (ShieldSlot, Item)
+pub struct ShieldSlot {}
+
+#[derive(Component)]
+#[meta]
+/// Relation, exclusive
+/// (RingSlot, Item)
+pub struct RingSlot {}
+
+#[derive(Component)]
+#[meta]
+/// Relation, exclusive
+/// (RangedSlot, Item)
+pub struct RangedSlot {}
+
+/// Puts the item into the slot given by its Equippable component.
+/// Whatever was in that slot before gets replaced.
+pub fn equip(unit: EntityView, item: EntityView) {
+    let slot = item.get::<&Equippable>(|eq| eq.slot);
+    match slot {
+        EquipSlot::Weapon => unit.add_first::<WeaponSlot>(item),
+        EquipSlot::Armor => unit.add_first::<ArmorSlot>(item),
+        EquipSlot::Shield => unit.add_first::<ShieldSlot>(item),
+        EquipSlot::Ring => unit.add_first::<RingSlot>(item),
+        EquipSlot::Ranged => unit.add_first::<RangedSlot>(item),
+    };
+}
+
+pub fn wielded_weapon(unit: EntityView) -> Weapon {
+    EquipSlot::Weapon
+        .item(unit)
+        .and_then(|item| item.try_get::<&Weapon>(|w| w.clone()))
+        .or_else(|| unit.try_get::<&Weapon>(|w| w.clone()))
+        .unwrap_or(UNARMED)
+}
+
+/// The weapon in the ranged slot, if it can shoot
+pub fn ranged_weapon(unit: EntityView) -> Option<(Weapon, Ranged)> {
+    let item = EquipSlot::Ranged.item(unit)?;
+    let weapon = item.try_get::<&Weapon>(|w| w.clone())?;
+    let ranged = item.try_get::<&Ranged>(|r| r.clone())?;
+    Some((weapon, ranged))
+}
+
+/// Summed up reduction of all equipped armor pieces
+pub fn armor_value(unit: EntityView) -> i32 {
+    EquipSlot::ALL
+        .iter()
+        .filter_map(|slot| slot.item(unit))
+        .filter_map(|item| item.try_get::<&Armor>(|a| a.reduction))
+        .sum()
+}
+
+#[derive(Component)]
+pub struct EquipmentComponents {}
+
+impl Module for EquipmentComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<Item>().meta().persist();
+        world.component_kf::<Equippable>().persist();
+        world.component_kf::<Weapon>().persist();
+        world.component_kf::<Ranged>().meta().persist();
+        world.component_kf::<Armor>().meta().persist();
+        for slot in [
+            world.component_kf::<WeaponSlot>().meta().persist(),
+            world.component_kf::<ArmorSlot>().meta().persist(),
+            world.component_kf::<ShieldSlot>().meta().persist(),
+            world.component_kf::<RingSlot>().meta().persist(),
+            world.component_kf::<RangedSlot>().meta().persist(),
+        ] {
+            slot.add::<flecs::Exclusive>();
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use derive_more::Display;
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+use crate::persist::{PersistExtension, PersistModule};
+use crate::util::flecs_extension::KfWorldExtensions;
+
+/// Enum relation, units without one count as monsters
+#[derive(Component, Display, Debug, Clone, Copy, PartialEq, Eq, DeJson, SerJson)]
+#[meta]
+#[repr(C)]
+pub enum Faction {
+    Player,
+    Monsters,
+    Animals,
+    Neutral,
+}
+
+impl Faction {
+    pub const ALL: [Faction; 4] = [
+        Faction::Player,
+        Faction::Monsters,
+        Faction::Animals,
+        Faction::Neutral,
+    ];
+
+    pub fn of(e: EntityView) -> Self {
+        e.try_get::<&Faction>(|f| *f).unwrap_or(Faction::Monsters)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, DeJson, SerJson)]
+pub enum Attitude {
+    #[display(fmt = "friendly")]
+    Friendly,
+    #[display(fmt = "neutral")]
+    Neutral,
+    #[display(fmt = "hostile")]
+    Hostile,
+}
+
+/// Singleton, how the faction of the row treats the faction of the column.
+/// Both are indexed in the order of `Faction::ALL`.
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+pub struct HostilityMatrix {
+    pub attitudes: Vec<Vec<Attitude>>,
+}
+
+impl Default for HostilityMatrix {
+    fn default() -> Self {
+        use Attitude::*;
+        Self {
+            attitudes: vec![
+                // player, monsters, animals, neutral
+                vec![Friendly, Hostile, Hostile, Neutral],
+                vec![Hostile, Friendly, Neutral, Neutral],
+                vec![Hostile, Neutral, Friendly, Neutral],
+                vec![Neutral, Neutral, Neutral, Friendly],
+            ],
+        }
+    }
+}
+
+impl HostilityMatrix {
+    pub fn attitude(&self, from: Faction, to: Faction) -> Attitude {
+        self.attitudes[from as usize][to as usize]
+    }
+}
+
+/// How `a` feels about `b`
+pub fn attitude(a: EntityView, b: EntityView) -> Attitude {
+    let (from, to) = (Faction::of(a), Faction::of(b));
+    a.world()
+        .get::<&HostilityMatrix>(|matrix| matrix.attitude(from, to))
+}
+
+pub fn is_hostile(a: EntityView, b: EntityView) -> bool {
+    attitude(a, b) == Attitude::Hostile
+}
+
+#[derive(Component)]
+pub struct FactionComponents {}
+
+impl Module for FactionComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<Faction>().meta().persist();
+        world.component_kf::<HostilityMatrix>().persist();
+        world.set(HostilityMatrix::default());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hostility_test() {
+        let world = World::new();
+        world.import::<FactionComponents>();
+
+        let player = world.entity().add_enum(Faction::Player);
+        let goblin = world.entity().add_enum(Faction::Monsters);
+        let unaligned = world.entity();
+        let rat = world.entity().add_enum(Faction::Animals);
+        let merchant = world.entity().add_enum(Faction::Neutral);
+
+        assert!(is_hostile(player, goblin));
+        assert!(is_hostile(goblin, player));
+        assert!(is_hostile(unaligned, player));
+        assert!(is_hostile(rat, player));
+        assert_eq!(Attitude::Neutral, attitude(goblin, rat));
+        assert_eq!(Attitude::Friendly, attitude(goblin, unaligned));
+        assert_eq!(Attitude::Neutral, attitude(player, merchant));
+    }
+}
@@ -1,11 +1,13 @@
 use crate::util::{
     flecs_extension::KfWorldExtensions,
     pos::{Direction, Pos},
+    rng::RngStreams,
 };
 use derive_more::Display;
 use flecs_ecs::prelude::*;
 use nanoserde::{DeJson, SerJson};
 
+use crate::message::{MessageArchive, MessageLog};
 use crate::persist::{PersistExtension, PersistModule, PersistTagExtension};
 
 #[derive(Component, Debug, DeJson, SerJson, Default)]
@@ -18,6 +20,14 @@ pub struct Unit {
     pub name: String,
 }
 
+/// Which sprite to draw, looked up by name in the description of the sheet
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct SpriteRef {
+    pub sheet: String,
+    pub name: String,
+}
+
 #[derive(Debug, Clone, Component, DeJson, SerJson)]
 #[meta]
 pub struct Health {
@@ -25,12 +35,7 @@ pub struct Health {
     pub current: i32,
 }
 
-#[derive(Component, Default, DeJson, SerJson)]
-pub struct MessageLog {
-    pub messages: Vec<String>,
-}
-
-#[derive(Component, Display)]
+#[derive(Component, Display, Debug, Clone, Copy, PartialEq, Eq, Hash, DeJson, SerJson)]
 #[meta]
 #[repr(C)]
 pub enum DamageKind {
@@ -38,6 +43,21 @@ pub enum DamageKind {
     Blunt,
     Pierce,
     Fire,
+    Poison,
+    Bleeding,
+    Starvation,
+}
+
+impl DamageKind {
+    pub const ALL: [DamageKind; 7] = [
+        DamageKind::Cutting,
+        DamageKind::Blunt,
+        DamageKind::Pierce,
+        DamageKind::Fire,
+        DamageKind::Poison,
+        DamageKind::Bleeding,
+        DamageKind::Starvation,
+    ];
 }
 
 #[derive(Component)]
@@ -66,6 +86,70 @@ impl DamageEvent {
     }
 }
 
+#[derive(Component)]
+#[meta]
+pub struct HealEvent {
+    pub amount: i32,
+}
+
+impl HealEvent {
+    pub fn create<'a>(
+        world: &'a World,
+        amount: i32,
+        origin: Entity,
+        targets: &[Entity],
+    ) -> EntityView<'a> {
+        let ev = world
+            .entity()
+            .set(Self { amount })
+            .add_first::<Origin>(origin);
+        for target in targets {
+            ev.add_first::<Target>(*target);
+        }
+        ev
+    }
+}
+
+/// Heals one hitpoint every `turns_per_hp` turns while hurt
+#[derive(Debug, Clone, Component, DeJson, SerJson)]
+#[meta]
+pub struct Regeneration {
+    pub turns_per_hp: i32,
+    pub progress: i32,
+}
+
+/// How far a unit can see, in tiles
+#[derive(Debug, Clone, Component, DeJson, SerJson)]
+#[meta]
+pub struct Perception {
+    pub range: i32,
+}
+
+impl Perception {
+    pub const DEFAULT_RANGE: i32 = 8;
+
+    pub fn of(e: EntityView) -> i32 {
+        e.try_get::<&Perception>(|p| p.range)
+            .unwrap_or(Self::DEFAULT_RANGE)
+    }
+}
+
+/// Lights up the tiles around it, on torches, lanterns and glowing monsters
+#[derive(Debug, Clone, Component, DeJson, SerJson)]
+#[meta]
+pub struct LightSource {
+    pub radius: i32,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+#[derive(Component)]
+#[meta]
+/// Relation, refreshed every frame from the field of view of the observer
+/// (CanSee, Unit)
+pub struct CanSee {}
+
 #[derive(Component)]
 #[meta]
 pub struct PushEvent {
@@ -105,8 +189,94 @@ pub struct Target {}
 #[meta]
 /// Relation
 /// (DamageEvent, Entity)
+/// also (Projectile, shooter), which outlives a frame and so gets persisted
 pub struct Origin {}
 
+/// Origin of harm nobody in particular is to blame for,
+/// like a shot whose shooter died while it was still flying
+pub fn environment(world: &World) -> EntityView {
+    world.entity_named("Environment")
+}
+
+#[derive(Component)]
+#[meta]
+/// Relation, exclusive
+/// (LastDamagedBy, Entity)
+/// Remembers who dealt the last damage, for kill credit
+pub struct LastDamagedBy {}
+
+/// Description of the last damage a unit took, so deaths can be explained
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+pub struct LastDamage {
+    pub kind: DamageKind,
+    pub amount: i32,
+    pub by: String,
+}
+
+/// Singleton, set once the player died
+#[derive(Component, Debug, Clone)]
+pub struct GameOver {
+    pub cause: String,
+}
+
+#[derive(Component)]
+/// Singleton tag
+/// Picked up by the main loop, which then rebuilds the world
+pub struct NewRunRequested {}
+
+/// Singleton, picked up by the main loop, which then moves the player
+/// `delta` levels deeper (or up for negative values)
+#[derive(Component, Debug, Clone)]
+pub struct LevelChange {
+    pub delta: i32,
+}
+
+/// Singleton, how deep the player currently is, starting at 1
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Depth {
+    pub current: i32,
+}
+
+impl Default for Depth {
+    fn default() -> Self {
+        Self { current: 1 }
+    }
+}
+
+/// Put on entities of levels the player left, which get disabled until they return
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct OnLevel {
+    pub depth: i32,
+}
+
+/// Singleton, counts the turns since the start of the run
+#[derive(Component, Debug, Default, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Turn {
+    pub number: u32,
+}
+
+/// Singleton, present while the player rests.
+/// Remembers the health of the last rested turn to notice being hurt.
+#[derive(Component, Debug)]
+pub struct Resting {
+    pub last_hp: i32,
+}
+
+/// Singleton, present while the player is still busy with a slow step,
+/// one more turn passes every frame until `turns` ran out
+#[derive(Component, Debug)]
+pub struct SlowMove {
+    pub turns: i32,
+}
+
+#[derive(Component)]
+/// Singleton tag
+/// Added when the player acted, everything else then gets to act once.
+pub struct TurnPassed {}
+
 #[derive(Component)]
 pub struct GameComponents {}
 
@@ -116,15 +286,42 @@ impl Module for GameComponents {
 
         world.component_kf::<Direction>().meta();
         world.component_kf::<Target>().meta();
-        world.component_kf::<Origin>().meta();
+        world.component_kf::<Origin>().meta().persist();
         world.component_kf::<DamageKind>().meta();
         world.component_kf::<DamageEvent>().meta();
         world.component_kf::<PushEvent>().meta();
+        world.component_kf::<HealEvent>().meta();
+        world.component_kf::<Resting>();
+        world.component_kf::<SlowMove>();
+        world.component_kf::<TurnPassed>();
+        world.component_kf::<GameOver>();
+        world.component_kf::<NewRunRequested>();
+        world.component_kf::<LevelChange>();
+        world.component_kf::<LastDamage>().persist();
+        world
+            .component_kf::<LastDamagedBy>()
+            .meta()
+            .persist()
+            .add::<flecs::Exclusive>();
         world.component_kf::<Pos>().meta().persist();
         world.component_kf::<Player>().meta().persist();
         world.component_kf::<Health>().meta().persist();
+        world.component_kf::<Regeneration>().meta().persist();
+        world.component_kf::<Perception>().meta().persist();
+        world.component_kf::<CanSee>().meta();
+        world.component_kf::<LightSource>().meta().persist();
         world.component_kf::<Unit>().meta().persist();
+        world.component_kf::<SpriteRef>().meta().persist();
         world.component_kf::<MessageLog>().persist();
         world.set(MessageLog::default());
+        world.component_kf::<MessageArchive>();
+        world.set(MessageArchive::default());
+        world.component_kf::<Turn>().meta().persist();
+        world.set(Turn::default());
+        world.component_kf::<Depth>().meta().persist();
+        world.set(Depth::default());
+        world.component_kf::<RngStreams>().persist();
+        world.set(RngStreams::default());
+        world.component_kf::<OnLevel>().meta().persist();
     }
 }
@@ -0,0 +1,167 @@
+use derive_more::Display;
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+use crate::persist::{PersistExtension, PersistModule};
+use crate::util::flecs_extension::KfWorldExtensions;
+
+/// Turns between two points of starvation damage
+pub const STARVATION_INTERVAL: u32 = 10;
+/// Nutrition of a corpse per hitpoint the unit had
+pub const CORPSE_NUTRITION_PER_HP: i32 = 40;
+
+/// Goes down by one every turn, eating fills it up again
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Satiation {
+    pub current: i32,
+    pub max: i32,
+}
+
+impl Default for Satiation {
+    fn default() -> Self {
+        Self {
+            current: 1500,
+            max: 2000,
+        }
+    }
+}
+
+impl Satiation {
+    pub fn state(&self) -> HungerState {
+        let percent = self.current * 100 / self.max.max(1);
+        match percent {
+            80.. => HungerState::Satiated,
+            25..=79 => HungerState::Normal,
+            10..=24 => HungerState::Hungry,
+            _ if self.current > 0 => HungerState::Weak,
+            _ => HungerState::Starving,
+        }
+    }
+}
+
+/// Ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display)]
+pub enum HungerState {
+    #[display(fmt = "satiated")]
+    Satiated,
+    #[display(fmt = "not hungry")]
+    Normal,
+    #[display(fmt = "hungry")]
+    Hungry,
+    #[display(fmt = "weak")]
+    Weak,
+    #[display(fmt = "starving")]
+    Starving,
+}
+
+impl HungerState {
+    /// Units without Satiation never get hungry
+    pub fn of(e: EntityView) -> Self {
+        e.try_get::<&Satiation>(|s| s.state())
+            .unwrap_or(HungerState::Normal)
+    }
+
+    /// Hungry units do not regenerate
+    pub fn regenerates(self) -> bool {
+        self <= HungerState::Normal
+    }
+
+    pub fn accuracy_penalty(self) -> i32 {
+        match self {
+            HungerState::Weak => 15,
+            HungerState::Starving => 25,
+            _ => 0,
+        }
+    }
+
+    pub fn evasion_penalty(self) -> i32 {
+        match self {
+            HungerState::Weak => 5,
+            HungerState::Starving => 10,
+            _ => 0,
+        }
+    }
+
+    /// Message for getting into this state, None for the harmless ones
+    pub fn warning(self) -> Option<&'static str> {
+        match self {
+            HungerState::Satiated | HungerState::Normal => None,
+            HungerState::Hungry => Some("You are getting hungry."),
+            HungerState::Weak => Some("You feel weak from hunger."),
+            HungerState::Starving => Some("You are starving!"),
+        }
+    }
+}
+
+/// On food items and corpses
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Edible {
+    pub nutrition: i32,
+}
+
+/// Eats up the food entity, returns the nutrition gained
+pub fn eat(eater: EntityView, food: EntityView) -> Option<i32> {
+    let nutrition = food.try_get::<&Edible>(|e| e.nutrition)?;
+    let gained = eater.try_get::<&mut Satiation>(|s| {
+        let before = s.current;
+        s.current = (s.current + nutrition).min(s.max);
+        s.current - before
+    })?;
+    food.destruct();
+    Some(gained)
+}
+
+#[derive(Component)]
+pub struct HungerComponents {}
+
+impl Module for HungerComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<Satiation>().meta().persist();
+        world.component_kf::<Edible>().meta().persist();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hunger_state_test() {
+        let mut s = Satiation {
+            current: 2000,
+            max: 2000,
+        };
+        assert_eq!(HungerState::Satiated, s.state());
+        s.current = 1000;
+        assert_eq!(HungerState::Normal, s.state());
+        s.current = 300;
+        assert_eq!(HungerState::Hungry, s.state());
+        s.current = 1;
+        assert_eq!(HungerState::Weak, s.state());
+        s.current = 0;
+        assert_eq!(HungerState::Starving, s.state());
+        assert!(!HungerState::Hungry.regenerates());
+    }
+
+    #[test]
+    fn eat_test() {
+        let world = World::new();
+        world.import::<HungerComponents>();
+
+        let player = world.entity().set(Satiation {
+            current: 1900,
+            max: 2000,
+        });
+        let ration = world.entity().set(Edible { nutrition: 800 });
+        let rock = world.entity();
+
+        assert_eq!(None, eat(player, rock));
+        assert_eq!(Some(100), eat(player, ration));
+        assert!(!ration.is_alive());
+        assert_eq!(2000, player.get::<&Satiation>(|s| s.current));
+    }
+}
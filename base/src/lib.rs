@@ -1,9 +1,36 @@
+use ability::AbilityComponents;
+use combat::CombatComponents;
+use equipment::EquipmentComponents;
+use faction::FactionComponents;
 use flecs_ecs::core::World;
 use game::GameComponents;
+use hunger::HungerComponents;
+use loot::LootComponents;
+use monster::MonsterComponents;
+use morgue::MorgueComponents;
 use persist::PersistModule;
+use progression::ProgressionComponents;
+use spawn::SpawnComponents;
+use stats::StatsComponents;
+use status::StatusComponents;
+use stealth::StealthComponents;
 
+pub mod ability;
+pub mod combat;
+pub mod equipment;
+pub mod faction;
 pub mod game;
+pub mod hunger;
+pub mod loot;
+pub mod message;
+pub mod monster;
+pub mod morgue;
 pub mod persist;
+pub mod progression;
+pub mod spawn;
+pub mod stats;
+pub mod status;
+pub mod stealth;
 pub mod util;
 pub mod vendored;
 pub use flecs_ecs;
@@ -12,4 +39,17 @@ pub use nanoserde;
 pub fn register_components(world: &World) {
     world.import::<PersistModule>();
     world.import::<GameComponents>();
+    world.import::<EquipmentComponents>();
+    world.import::<StatusComponents>();
+    world.import::<CombatComponents>();
+    world.import::<ProgressionComponents>();
+    world.import::<LootComponents>();
+    world.import::<AbilityComponents>();
+    world.import::<FactionComponents>();
+    world.import::<MonsterComponents>();
+    world.import::<SpawnComponents>();
+    world.import::<StealthComponents>();
+    world.import::<HungerComponents>();
+    world.import::<MorgueComponents>();
+    world.import::<StatsComponents>();
 }
@@ -0,0 +1,136 @@
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+use crate::equipment::{Armor, EquipSlot, Equippable, Item, Ranged, Weapon};
+use crate::game::{Health, SpriteRef, Unit};
+use crate::hunger::{Edible, CORPSE_NUTRITION_PER_HP};
+use crate::persist::{PersistExtension, PersistModule};
+use crate::util::flecs_extension::KfWorldExtensions;
+use crate::util::pos::Pos;
+use crate::util::rng::Rng;
+
+/// Turns until a freshly created corpse rots away
+pub const CORPSE_DECAY_TURNS: i32 = 100;
+
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Corpse {
+    /// name of the unit that died
+    pub name: String,
+}
+
+/// Entity gets destroyed once this runs out
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Decay {
+    pub turns: i32,
+}
+
+/// Everything needed to create an item entity
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct ItemTemplate {
+    pub name: String,
+    pub slot: Option<EquipSlot>,
+    pub weapon: Option<Weapon>,
+    pub ranged: Option<Ranged>,
+    pub armor: Option<Armor>,
+    #[nserde(default)]
+    pub edible: Option<Edible>,
+}
+
+impl ItemTemplate {
+    pub fn spawn<'a>(&self, world: &'a World) -> EntityView<'a> {
+        let item = world.entity().set(Item {
+            name: self.name.clone(),
+        });
+        if let Some(slot) = self.slot {
+            item.set(Equippable { slot });
+        }
+        if let Some(weapon) = &self.weapon {
+            item.set(weapon.clone());
+        }
+        if let Some(ranged) = &self.ranged {
+            item.set(ranged.clone());
+        }
+        if let Some(armor) = &self.armor {
+            item.set(armor.clone());
+        }
+        if let Some(edible) = &self.edible {
+            item.set(edible.clone());
+        }
+        item
+    }
+}
+
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct LootEntry {
+    /// in percent
+    pub chance: i32,
+    pub item: ItemTemplate,
+}
+
+/// Rolled once when the unit dies, every entry is checked on its own
+#[derive(Component, Debug, Clone, Default, DeJson, SerJson)]
+pub struct LootTable {
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    pub fn roll(&self, rng: &mut Rng) -> Vec<&ItemTemplate> {
+        self.entries
+            .iter()
+            .filter(|entry| rng.chance(entry.chance))
+            .map(|entry| &entry.item)
+            .collect()
+    }
+}
+
+/// Leaves a corpse and everything the unit carried at its position.
+pub fn leave_remains(unit: EntityView, rng: &mut Rng) -> Option<Entity> {
+    let world = unit.world();
+    let pos = unit.try_get::<&Pos>(|pos| *pos)?;
+    let name = unit
+        .try_get::<&Unit>(|u| u.name.clone())
+        .unwrap_or_default();
+    let max_hp = unit.try_get::<&Health>(|hp| hp.max).unwrap_or(1);
+
+    for slot in EquipSlot::ALL {
+        if let Some(item) = slot.item(unit) {
+            item.set(pos);
+        }
+    }
+    let loot = unit
+        .try_get::<&LootTable>(|table| table.roll(rng).into_iter().cloned().collect())
+        .unwrap_or_else(Vec::new);
+    for template in loot {
+        template.spawn(&world).set(pos);
+    }
+
+    let corpse = world
+        .entity()
+        .set(Corpse { name })
+        .set(Decay {
+            turns: CORPSE_DECAY_TURNS,
+        })
+        .set(Edible {
+            nutrition: max_hp * CORPSE_NUTRITION_PER_HP,
+        })
+        .set(pos);
+    if let Some(sprite) = unit.try_get::<&SpriteRef>(|s| s.clone()) {
+        corpse.set(sprite);
+    }
+    Some(*corpse)
+}
+
+#[derive(Component)]
+pub struct LootComponents {}
+
+impl Module for LootComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<Corpse>().meta().persist();
+        world.component_kf::<Decay>().meta().persist();
+        world.component_kf::<LootTable>().persist();
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use derive_more::Display;
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+/// Messages beyond this get moved from the log into the archive file
+pub const MESSAGE_LOG_CAP: usize = 200;
+pub const MESSAGE_ARCHIVE_PATH: &str = "message_archive.log";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, DeJson, SerJson)]
+pub enum MessageCategory {
+    Combat,
+    Movement,
+    Status,
+    Progression,
+    System,
+}
+
+impl MessageCategory {
+    pub const ALL: [MessageCategory; 5] = [
+        MessageCategory::Combat,
+        MessageCategory::Movement,
+        MessageCategory::Status,
+        MessageCategory::Progression,
+        MessageCategory::System,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeJson, SerJson)]
+pub enum Severity {
+    Info,
+    Good,
+    Warning,
+    Danger,
+}
+
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct Message {
+    pub turn: u32,
+    pub category: MessageCategory,
+    pub severity: Severity,
+    pub text: String,
+    /// ids of the entities the message is about
+    pub entities: Vec<u64>,
+}
+
+impl Message {
+    pub fn severity(&mut self, severity: Severity) -> &mut Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn about(&mut self, e: impl Into<Entity>) -> &mut Self {
+        let e: Entity = e.into();
+        self.entities.push(e.0);
+        self
+    }
+}
+
+#[derive(Component, Default, DeJson, SerJson)]
+pub struct MessageLog {
+    pub messages: Vec<Message>,
+    /// stamped onto new messages, kept in sync with the Turn singleton
+    pub turn: u32,
+}
+
+/// Where the message log overflow goes, `None` drops it instead
+#[derive(Component)]
+pub struct MessageArchive {
+    pub path: Option<String>,
+}
+
+impl Default for MessageArchive {
+    fn default() -> Self {
+        Self {
+            path: Some(MESSAGE_ARCHIVE_PATH.to_string()),
+        }
+    }
+}
+
+impl MessageLog {
+    /// Adds an informational message, use the returned reference to refine it
+    pub fn add(&mut self, category: MessageCategory, text: impl Into<String>) -> &mut Message {
+        self.messages.push(Message {
+            turn: self.turn,
+            category,
+            severity: Severity::Info,
+            text: text.into(),
+            entities: Vec::new(),
+        });
+        self.messages.last_mut().unwrap()
+    }
+
+    /// Moves everything beyond the cap into the archive file, or drops it without one
+    pub fn archive_overflow(&mut self, path: Option<&str>) -> std::io::Result<()> {
+        if self.messages.len() <= MESSAGE_LOG_CAP {
+            return Ok(());
+        }
+        let overflow = self.messages.len() - MESSAGE_LOG_CAP;
+        let Some(path) = path else {
+            self.messages.drain(..overflow);
+            return Ok(());
+        };
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        for msg in self.messages.drain(..overflow) {
+            writeln!(file, "[{}] {}: {}", msg.turn, msg.category, msg.text)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn archive_overflow_test() {
+        let path = std::env::temp_dir().join("flecsirogue_archive_test.log");
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let mut ml = MessageLog::default();
+        for i in 0..MESSAGE_LOG_CAP + 3 {
+            ml.add(MessageCategory::System, format!("message {i}"));
+        }
+        ml.archive_overflow(Some(path)).unwrap();
+        assert_eq!(MESSAGE_LOG_CAP, ml.messages.len());
+        assert_eq!("message 3", ml.messages[0].text);
+        let archived = std::fs::read_to_string(path).unwrap();
+        assert_eq!(3, archived.lines().count());
+        assert!(archived.starts_with("[0] System: message 0"));
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use derive_more::Display;
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, DeJsonErr, SerJson};
+
+use crate::ability::{grant_ability, Ability, Mana};
+use crate::combat::CombatStats;
+use crate::equipment::{equip, Weapon};
+use crate::faction::Faction;
+use crate::game::{DamageKind, Health, LightSource, Perception, SpriteRef, Unit};
+use crate::loot::{ItemTemplate, LootEntry, LootTable};
+use crate::persist::{PersistExtension, PersistModule};
+use crate::stealth::Awareness;
+use crate::util::flecs_extension::KfWorldExtensions;
+use crate::util::pos::Pos;
+
+/// Enum relation, how a monster fights. Units without one are aggressive.
+#[derive(Component, Display, Debug, Clone, Copy, PartialEq, Eq, DeJson, SerJson)]
+#[meta]
+#[repr(C)]
+pub enum AiProfile {
+    /// walks up to its enemies, casting and shooting on the way
+    Aggressive,
+    /// backs off from enemies close by while it has something to shoot or cast
+    Skirmisher,
+    /// runs away when badly hurt
+    Cowardly,
+    /// never leaves its tile
+    Stationary,
+}
+
+impl AiProfile {
+    pub fn of(e: EntityView) -> Self {
+        e.try_get::<&AiProfile>(|p| *p)
+            .unwrap_or(AiProfile::Aggressive)
+    }
+}
+
+/// Energy gained per turn, a unit acts once for every NORMAL_SPEED energy
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Speed {
+    pub speed: i32,
+    pub energy: i32,
+}
+
+pub const NORMAL_SPEED: i32 = 100;
+
+impl Speed {
+    /// Adds a turn worth of energy and returns how many actions it pays for
+    pub fn actions(&mut self) -> i32 {
+        self.energy += self.speed;
+        let actions = self.energy / NORMAL_SPEED;
+        self.energy %= NORMAL_SPEED;
+        actions
+    }
+}
+
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct Resistance {
+    pub kind: DamageKind,
+    /// of the damage that gets ignored, negative for weaknesses
+    pub percent: i32,
+}
+
+#[derive(Component, Debug, Clone, Default, DeJson, SerJson)]
+pub struct Resistances {
+    pub entries: Vec<Resistance>,
+}
+
+impl Resistances {
+    pub fn percent(&self, kind: DamageKind) -> i32 {
+        self.entries
+            .iter()
+            .filter(|r| r.kind == kind)
+            .map(|r| r.percent)
+            .sum()
+    }
+
+    /// The damage left after resisting
+    pub fn apply(&self, kind: DamageKind, amount: i32) -> i32 {
+        (amount * (100 - self.percent(kind)) / 100).max(0)
+    }
+}
+
+/// One entry of the monster data file
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub sprite: SpriteRef,
+    pub faction: Faction,
+    pub ai: AiProfile,
+    pub health: i32,
+    pub stats: CombatStats,
+    pub speed: i32,
+    /// in tiles
+    pub perception: i32,
+    /// natural attack, used when nothing is in the weapon slot
+    pub attack: Option<Weapon>,
+    pub mana: Option<Mana>,
+    /// for monsters that glow
+    pub light: Option<LightSource>,
+    /// in percent, rolled once for the whole group the monster spawns in
+    #[nserde(default)]
+    pub sleep_chance: i32,
+    #[nserde(default)]
+    pub resistances: Vec<Resistance>,
+    #[nserde(default)]
+    pub equipment: Vec<ItemTemplate>,
+    #[nserde(default)]
+    pub abilities: Vec<Ability>,
+    #[nserde(default)]
+    pub loot: Vec<LootEntry>,
+}
+
+impl MonsterTemplate {
+    /// Everything instances share, equipment and abilities get created per instance
+    fn create_prefab<'a>(&self, world: &'a World) -> EntityView<'a> {
+        let prefab = world
+            .prefab()
+            .set(Unit {
+                name: self.name.clone(),
+            })
+            .set(Health {
+                max: self.health,
+                current: self.health,
+            })
+            .set(self.stats.clone())
+            .set(self.sprite.clone())
+            .set(Speed {
+                speed: self.speed,
+                energy: 0,
+            })
+            .set(Perception {
+                range: self.perception,
+            })
+            .add_enum(self.faction)
+            .add_enum(self.ai);
+        if let Some(attack) = &self.attack {
+            prefab.set(attack.clone());
+        }
+        if let Some(mana) = &self.mana {
+            prefab.set(mana.clone());
+        }
+        if let Some(light) = &self.light {
+            prefab.set(light.clone());
+        }
+        if !self.resistances.is_empty() {
+            prefab.set(Resistances {
+                entries: self.resistances.clone(),
+            });
+        }
+        if !self.loot.is_empty() {
+            prefab.set(LootTable {
+                entries: self.loot.clone(),
+            });
+        }
+        prefab
+    }
+}
+
+/// Singleton, the loaded monster templates and the prefabs made from them
+#[derive(Component, Default)]
+pub struct Bestiary {
+    pub templates: HashMap<String, MonsterTemplate>,
+    pub prefabs: HashMap<String, Entity>,
+}
+
+/// Reads the monster data file and creates a prefab for every entry
+pub fn load_bestiary(world: &World, json: &str) -> Result<(), DeJsonErr> {
+    let templates: Vec<MonsterTemplate> = DeJson::deserialize_json(json)?;
+    let mut bestiary = Bestiary::default();
+    for template in templates {
+        let prefab = template.create_prefab(world);
+        bestiary.prefabs.insert(template.name.clone(), *prefab);
+        bestiary.templates.insert(template.name.clone(), template);
+    }
+    world.set(bestiary);
+    Ok(())
+}
+
+/// Instantiates the prefab of the named monster, with its own equipment and abilities.
+/// It starts out unaware of its enemies.
+pub fn spawn_monster(world: &World, name: &str, pos: Pos) -> Option<Entity> {
+    let (prefab, template) = world.get::<&Bestiary>(|b| {
+        let prefab = *b.prefabs.get(name)?;
+        Some((prefab, b.templates.get(name)?.clone()))
+    })?;
+    let monster = world
+        .entity()
+        .is_a_id(prefab)
+        .set(pos)
+        .add_enum(Awareness::Unaware);
+    for item in &template.equipment {
+        equip(monster, item.spawn(world));
+    }
+    for ability in template.abilities {
+        grant_ability(monster, ability);
+    }
+    Some(*monster)
+}
+
+#[derive(Component)]
+pub struct MonsterComponents {}
+
+impl Module for MonsterComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<AiProfile>().meta().persist();
+        world.component_kf::<Speed>().meta().persist();
+        world.component_kf::<Resistances>().persist();
+        world.component_kf::<Bestiary>();
+        world.set(Bestiary::default());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::equipment::wielded_weapon;
+    use crate::register_components;
+
+    const BAT: &str = r#"[{
+        "name": "Bat",
+        "sprite": { "sheet": "monsters", "name": "giant bat" },
+        "faction": "Animals",
+        "ai": "Cowardly",
+        "health": 2,
+        "stats": { "accuracy": 60, "evasion": 30, "crit_chance": 5 },
+        "speed": 200,
+        "perception": 6,
+        "attack": { "kind": "Pierce", "min_damage": 1, "max_damage": 1, "push": 0, "on_hit": [] },
+        "mana": null,
+        "light": null,
+        "resistances": [{ "kind": "Poison", "percent": 100 }]
+    }]"#;
+
+    #[test]
+    fn spawn_monster_test() {
+        let world = World::new();
+        register_components(&world);
+        load_bestiary(&world, BAT).unwrap();
+
+        assert!(spawn_monster(&world, "Dragon", Pos::new(0, 0)).is_none());
+        let bat = spawn_monster(&world, "Bat", Pos::new(2, 3)).unwrap();
+        let bat = world.entity_from_id(bat);
+        let other = world.entity_from_id(spawn_monster(&world, "Bat", Pos::new(4, 3)).unwrap());
+
+        assert_eq!(Faction::Animals, Faction::of(bat));
+        assert_eq!(AiProfile::Cowardly, AiProfile::of(bat));
+        assert_eq!(Pos::new(2, 3), bat.get::<&Pos>(|p| *p));
+        assert_eq!(DamageKind::Pierce, wielded_weapon(bat).kind);
+        assert_eq!(
+            0,
+            bat.get::<&Resistances>(|r| r.apply(DamageKind::Poison, 3))
+        );
+
+        // instances do not share their health
+        bat.get::<&mut Health>(|hp| hp.current -= 1);
+        assert_eq!(1, bat.get::<&Health>(|hp| hp.current));
+        assert_eq!(2, other.get::<&Health>(|hp| hp.current));
+    }
+
+    #[test]
+    fn speed_test() {
+        let mut slow = Speed {
+            speed: 50,
+            energy: 0,
+        };
+        assert_eq!(0, slow.actions());
+        assert_eq!(1, slow.actions());
+        let mut fast = Speed {
+            speed: 200,
+            energy: 0,
+        };
+        assert_eq!(2, fast.actions());
+    }
+
+    #[test]
+    fn resistances_test() {
+        let resistances = Resistances {
+            entries: vec![
+                Resistance {
+                    kind: DamageKind::Fire,
+                    percent: 50,
+                },
+                Resistance {
+                    kind: DamageKind::Blunt,
+                    percent: -100,
+                },
+            ],
+        };
+        assert_eq!(2, resistances.apply(DamageKind::Fire, 4));
+        assert_eq!(6, resistances.apply(DamageKind::Blunt, 3));
+        assert_eq!(3, resistances.apply(DamageKind::Cutting, 3));
+    }
+}
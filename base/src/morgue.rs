@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+use crate::equipment::{EquipSlot, Item};
+use crate::game::{Depth, Turn, Unit};
+use crate::message::MessageLog;
+use crate::persist::{PersistExtension, PersistModule};
+use crate::progression::Experience;
+use crate::util::flecs_extension::KfWorldExtensions;
+
+/// Morgue files of finished runs end up in here
+pub const MORGUE_DIR: &str = "morgue";
+pub const HIGH_SCORE_PATH: &str = "highscores.json";
+/// Only the best runs are kept
+pub const HIGH_SCORE_CAP: usize = 10;
+/// How many of the last messages go into the morgue file
+pub const MORGUE_MESSAGES: usize = 20;
+/// Score for every level below the first one
+pub const DEPTH_SCORE: i32 = 50;
+
+/// Singleton, what the player killed during the run, by unit name
+#[derive(Component, Debug, Clone, Default, DeJson, SerJson)]
+pub struct Kills {
+    pub by_name: HashMap<String, i32>,
+}
+
+impl Kills {
+    pub fn add(&mut self, name: &str) {
+        *self.by_name.entry(name.to_owned()).or_default() += 1;
+    }
+
+    pub fn total(&self) -> i32 {
+        self.by_name.values().sum()
+    }
+
+    /// Most killed first, ties by name
+    pub fn sorted(&self) -> Vec<(String, i32)> {
+        let mut kills: Vec<_> = self
+            .by_name
+            .iter()
+            .map(|(name, count)| (name.clone(), *count))
+            .collect();
+        kills.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        kills
+    }
+}
+
+/// Everything worth remembering about a finished run
+#[derive(Debug, Clone, Default)]
+pub struct Morgue {
+    pub name: String,
+    pub cause: String,
+    pub level: i32,
+    pub xp: i32,
+    pub depth: i32,
+    pub turns: u32,
+    /// (slot, item name)
+    pub equipment: Vec<(String, String)>,
+    pub kills: Vec<(String, i32)>,
+    pub messages: Vec<String>,
+    /// The final level, one line per row
+    pub map: Vec<String>,
+}
+
+impl Morgue {
+    /// Gathers everything but the map, which only the frontend knows how to draw
+    pub fn collect(player: EntityView, cause: &str) -> Self {
+        let world = player.world();
+        let (level, xp) = player
+            .try_get::<&Experience>(|exp| (exp.level, exp.xp))
+            .unwrap_or((1, 0));
+        let equipment = EquipSlot::ALL
+            .into_iter()
+            .map(|slot| {
+                let item = slot
+                    .item(player)
+                    .and_then(|item| item.try_get::<&Item>(|i| i.name.clone()))
+                    .unwrap_or_else(|| "-".into());
+                (format!("{slot:?}"), item)
+            })
+            .collect();
+        let messages = world.get::<&MessageLog>(|ml| {
+            let skip = ml.messages.len().saturating_sub(MORGUE_MESSAGES);
+            ml.messages[skip..]
+                .iter()
+                .map(|msg| format!("[{}] {}", msg.turn, msg.text))
+                .collect()
+        });
+        Self {
+            name: player
+                .try_get::<&Unit>(|u| u.name.clone())
+                .unwrap_or_default(),
+            cause: cause.to_owned(),
+            level,
+            xp,
+            depth: world.get::<&Depth>(|d| d.current),
+            turns: world.get::<&Turn>(|t| t.number),
+            equipment,
+            kills: world.get::<&Kills>(|k| k.sorted()),
+            messages,
+            map: Vec::new(),
+        }
+    }
+
+    pub fn score(&self) -> i32 {
+        self.xp + DEPTH_SCORE * (self.depth - 1).max(0)
+    }
+
+    pub fn high_score(&self) -> HighScore {
+        HighScore {
+            name: self.name.clone(),
+            score: self.score(),
+            level: self.level,
+            depth: self.depth,
+            turns: self.turns,
+            cause: self.cause.clone(),
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut s = String::new();
+        let _ = writeln!(s, "{}, level {}", self.name, self.level);
+        let _ = writeln!(s, "{}", self.cause);
+        let _ = writeln!(
+            s,
+            "Reached depth {} after {} turns with {} experience, scoring {}.",
+            self.depth,
+            self.turns,
+            self.xp,
+            self.score()
+        );
+
+        let _ = writeln!(s, "\nEquipment");
+        for (slot, item) in &self.equipment {
+            let _ = writeln!(s, "  {slot}: {item}");
+        }
+
+        let total: i32 = self.kills.iter().map(|(_, count)| count).sum();
+        let _ = writeln!(s, "\nKills ({total})");
+        for (name, count) in &self.kills {
+            let _ = writeln!(s, "  {count:>4} {name}");
+        }
+
+        let _ = writeln!(s, "\nLast messages");
+        for msg in &self.messages {
+            let _ = writeln!(s, "  {msg}");
+        }
+
+        let _ = writeln!(s, "\nMap");
+        for row in &self.map {
+            let _ = writeln!(s, "{row}");
+        }
+        s
+    }
+
+    /// Writes the morgue file into `dir`, named after the character and the time of death
+    pub fn write(&self, dir: &str) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let path = PathBuf::from(dir).join(format!("{}-{secs}.txt", self.name.to_lowercase()));
+        std::fs::write(&path, self.render())?;
+        Ok(path)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, DeJson, SerJson)]
+pub struct HighScore {
+    pub name: String,
+    pub score: i32,
+    pub level: i32,
+    pub depth: i32,
+    pub turns: u32,
+    pub cause: String,
+}
+
+/// Singleton, the best runs so far, best first
+#[derive(Component, Debug, Clone, Default, DeJson, SerJson)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    /// A missing or broken file gives an empty table
+    pub fn load(path: &str) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|json| HighScores::deserialize_json(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.serialize_json())
+    }
+
+    /// Returns the rank, starting at 0, if the entry made it into the table
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        // later runs rank below earlier ones with the same score
+        let rank = self
+            .entries
+            .iter()
+            .position(|e| e.score < entry.score)
+            .unwrap_or(self.entries.len());
+        if rank >= HIGH_SCORE_CAP {
+            return None;
+        }
+        self.entries.insert(rank, entry);
+        self.entries.truncate(HIGH_SCORE_CAP);
+        Some(rank)
+    }
+}
+
+#[derive(Component)]
+pub struct MorgueComponents {}
+
+impl Module for MorgueComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<Kills>().persist();
+        world.set(Kills::default());
+        world.component_kf::<HighScores>();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(name: &str, score: i32) -> HighScore {
+        HighScore {
+            name: name.into(),
+            score,
+            level: 1,
+            depth: 1,
+            turns: 10,
+            cause: "Killed by a test.".into(),
+        }
+    }
+
+    #[test]
+    fn kills_test() {
+        let mut kills = Kills::default();
+        kills.add("Goblin");
+        kills.add("Orc");
+        kills.add("Goblin");
+        kills.add("Bat");
+        assert_eq!(4, kills.total());
+        assert_eq!(
+            vec![
+                ("Goblin".to_owned(), 2),
+                ("Bat".to_owned(), 1),
+                ("Orc".to_owned(), 1)
+            ],
+            kills.sorted()
+        );
+    }
+
+    #[test]
+    fn high_score_test() {
+        let mut scores = HighScores::default();
+        for i in 0..HIGH_SCORE_CAP as i32 {
+            assert!(scores.insert(entry("Filler", 10 * (i + 1))).is_some());
+        }
+        assert_eq!(None, scores.insert(entry("Loser", 5)));
+        assert_eq!(Some(2), scores.insert(entry("Tied", 90)));
+        assert_eq!(HIGH_SCORE_CAP, scores.entries.len());
+        assert_eq!(100, scores.entries[0].score);
+        assert_eq!("Tied", scores.entries[2].name);
+        assert_eq!(20, scores.entries.last().unwrap().score);
+
+        let path = std::env::temp_dir().join("flecsirogue_highscores_test.json");
+        let path = path.to_str().unwrap();
+        scores.save(path).unwrap();
+        assert_eq!(scores.entries, HighScores::load(path).entries);
+        assert!(HighScores::load("does/not/exist.json").entries.is_empty());
+    }
+
+    #[test]
+    fn render_test() {
+        let morgue = Morgue {
+            name: "Player".into(),
+            cause: "Killed by Goblin with 3 Pierce damage.".into(),
+            level: 3,
+            xp: 50,
+            depth: 3,
+            turns: 200,
+            equipment: vec![("Weapon".into(), "Short Sword".into())],
+            kills: vec![("Goblin".into(), 2)],
+            messages: vec!["[199] Player dies.".into()],
+            map: vec!["#####".into(), "#@.>#".into(), "#####".into()],
+        };
+        assert_eq!(150, morgue.score());
+        let text = morgue.render();
+        assert!(text.contains("Weapon: Short Sword"));
+        assert!(text.contains("Kills (2)"));
+        assert!(text.contains("[199] Player dies."));
+        assert!(text.ends_with("#@.>#\n#####\n"));
+    }
+}
@@ -106,6 +106,9 @@ pub fn serialize_world(world: &World) -> Vec<SerializedEntity> {
         .with::<Persist>()
         .set_src_name("$comp")
         .without_name("flecs.meta.member") // not sure how access this via type, since its a C type
+        // makes the query match disabled entities too, like the ones of stored levels
+        .with::<flecs::Disabled>()
+        .optional()
         .set_cached()
         .build();
     let mut es = HashSet::new(); // want to have all entities only once
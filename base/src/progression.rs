@@ -0,0 +1,79 @@
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+use crate::game::Health;
+use crate::persist::{PersistExtension, PersistModule};
+use crate::util::flecs_extension::KfWorldExtensions;
+
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Experience {
+    pub level: i32,
+    /// total experience collected, not reset on level up
+    pub xp: i32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self { level: 1, xp: 0 }
+    }
+}
+
+impl Experience {
+    /// Total experience needed to leave the given level
+    pub fn threshold(level: i32) -> i32 {
+        10 * level * level
+    }
+
+    pub fn next_threshold(&self) -> i32 {
+        Self::threshold(self.level)
+    }
+
+    /// Raises the level as far as the collected experience allows,
+    /// returns by how much.
+    pub fn level_up(&mut self) -> i32 {
+        let mut gained = 0;
+        while self.xp >= self.next_threshold() {
+            self.level += 1;
+            gained += 1;
+        }
+        gained
+    }
+}
+
+pub const LEVEL_UP_HEALTH: i32 = 3;
+pub const LEVEL_UP_ACCURACY: i32 = 5;
+
+/// Experience granted for killing the given unit
+pub fn kill_reward(victim: EntityView) -> i32 {
+    let health = victim.try_get::<&Health>(|hp| hp.max).unwrap_or(1);
+    let level = victim.try_get::<&Experience>(|exp| exp.level).unwrap_or(1);
+    health + 2 * (level - 1)
+}
+
+#[derive(Component)]
+pub struct ProgressionComponents {}
+
+impl Module for ProgressionComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<Experience>().meta().persist();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn level_up_test() {
+        let mut exp = Experience::default();
+        exp.xp = 9;
+        assert_eq!(0, exp.level_up());
+        exp.xp = 45;
+        assert_eq!(2, exp.level_up());
+        assert_eq!(3, exp.level);
+        assert_eq!(90, exp.next_threshold());
+    }
+}
@@ -0,0 +1,305 @@
+use std::cmp::Reverse;
+
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, DeJsonErr, SerJson};
+
+use crate::monster::{spawn_monster, Bestiary};
+use crate::persist::{PersistExtension, PersistModule};
+use crate::stealth::Awareness;
+use crate::util::flecs_extension::KfWorldExtensions;
+use crate::util::pos::Pos;
+use crate::util::rng::{Rng, RngStreams};
+
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct EncounterMember {
+    /// name in the bestiary
+    pub monster: String,
+    pub min: i32,
+    pub max: i32,
+}
+
+/// Monsters that spawn next to each other, the first one to spawn leads the rest
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct Encounter {
+    pub name: String,
+    pub weight: i32,
+    /// inclusive on both ends
+    pub min_depth: i32,
+    pub max_depth: i32,
+    pub members: Vec<EncounterMember>,
+}
+
+impl Encounter {
+    pub fn allowed_at(&self, depth: i32) -> bool {
+        (self.min_depth..=self.max_depth).contains(&depth)
+    }
+
+    /// Monster names in spawn order, the leader first
+    pub fn roll_members(&self, rng: &mut Rng) -> Vec<String> {
+        let mut names = Vec::new();
+        for member in &self.members {
+            for _ in 0..rng.range(member.min, member.max) {
+                names.push(member.monster.clone());
+            }
+        }
+        names
+    }
+}
+
+/// How often monsters wander into the current level
+#[derive(Debug, Clone, Default, DeJson, SerJson)]
+pub struct WanderingRules {
+    /// turns between two checks, 0 turns wandering monsters off
+    pub every_turns: i32,
+    /// in percent, per check
+    pub chance: i32,
+    /// no more wanderers once the level holds this many monsters
+    pub max_monsters: i32,
+}
+
+/// Singleton, loaded from the spawn data file
+#[derive(Component, Debug, Clone, Default, DeJson, SerJson)]
+pub struct SpawnTable {
+    pub encounters: Vec<Encounter>,
+    /// encounters on a fresh level at depth 1
+    pub base_encounters: i32,
+    pub encounters_per_depth: i32,
+    /// in tiles, nothing spawns closer to where the player arrives
+    pub min_start_distance: i32,
+    pub wandering: WanderingRules,
+}
+
+impl SpawnTable {
+    pub fn encounter_count(&self, depth: i32) -> i32 {
+        self.base_encounters + self.encounters_per_depth * (depth - 1)
+    }
+
+    /// Weighted pick among the encounters allowed at the depth
+    pub fn choose(&self, depth: i32, rng: &mut Rng) -> Option<&Encounter> {
+        let allowed: Vec<_> = self
+            .encounters
+            .iter()
+            .filter(|e| e.allowed_at(depth) && e.weight > 0)
+            .collect();
+        let total: i32 = allowed.iter().map(|e| e.weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = rng.range(1, total);
+        for encounter in allowed {
+            if roll <= encounter.weight {
+                return Some(encounter);
+            }
+            roll -= encounter.weight;
+        }
+        None
+    }
+}
+
+/// Relation, group members stay close to their leader while idle
+/// (Follows, leader)
+#[derive(Component)]
+#[meta]
+pub struct Follows {}
+
+/// Turns since the last check for wandering monsters
+#[derive(Component, Debug, Clone, Default, DeJson, SerJson)]
+#[meta]
+pub struct WanderingClock {
+    pub turns: i32,
+}
+
+pub fn load_spawn_table(world: &World, json: &str) -> Result<(), DeJsonErr> {
+    let table: SpawnTable = DeJson::deserialize_json(json)?;
+    world.set(table);
+    Ok(())
+}
+
+/// Spawns the members on the free positions closest to the anchor
+/// and removes the positions it used. The leader decides if the whole group sleeps.
+pub fn spawn_encounter(
+    world: &World,
+    encounter: &Encounter,
+    rng: &mut Rng,
+    free: &mut Vec<Pos>,
+    anchor: Pos,
+) -> Vec<Entity> {
+    free.sort_by_key(|p| Reverse(p.distance(anchor)));
+    let names = encounter.roll_members(rng);
+    let sleep_chance = names.first().map_or(0, |leader| {
+        world.get::<&Bestiary>(|b| b.templates.get(leader).map_or(0, |t| t.sleep_chance))
+    });
+    let asleep = rng.chance(sleep_chance);
+    let mut spawned = Vec::new();
+    for name in names {
+        let Some(pos) = free.pop() else {
+            break;
+        };
+        if let Some(monster) = spawn_monster(world, &name, pos) {
+            if asleep {
+                world.entity_from_id(monster).add_enum(Awareness::Asleep);
+            }
+            spawned.push(monster);
+        }
+    }
+    if let Some((leader, members)) = spawned.split_first() {
+        for member in members {
+            world.entity_from_id(*member).add_first::<Follows>(*leader);
+        }
+    }
+    spawned
+}
+
+/// Picks a random anchor among the free positions far enough from `start`
+/// and spawns an encounter of the depth there
+pub fn spawn_random_encounter(
+    world: &World,
+    depth: i32,
+    rng: &mut Rng,
+    free: &mut Vec<Pos>,
+    start: Pos,
+) -> Vec<Entity> {
+    let Some(table) = world.try_get::<&SpawnTable>(|t| t.clone()) else {
+        return Vec::new();
+    };
+    free.retain(|p| p.distance(start) >= table.min_start_distance);
+    if free.is_empty() {
+        return Vec::new();
+    }
+    let anchor = free[rng.range(0, free.len() as i32 - 1) as usize];
+    match table.choose(depth, rng) {
+        Some(encounter) => spawn_encounter(world, encounter, rng, free, anchor),
+        None => Vec::new(),
+    }
+}
+
+/// Fills a fresh level with as many encounters as its depth calls for
+pub fn populate_level(world: &World, depth: i32, mut free: Vec<Pos>, start: Pos) {
+    let count = world
+        .try_get::<&SpawnTable>(|t| t.encounter_count(depth))
+        .unwrap_or(0);
+    let mut rng = world.get::<&RngStreams>(|streams| streams.spawns.clone());
+    for _ in 0..count {
+        spawn_random_encounter(world, depth, &mut rng, &mut free, start);
+    }
+    world.get::<&mut RngStreams>(|streams| streams.spawns = rng);
+}
+
+#[derive(Component)]
+pub struct SpawnComponents {}
+
+impl Module for SpawnComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<SpawnTable>();
+        world.component_kf::<Follows>().meta().persist();
+        world.component_kf::<WanderingClock>().meta().persist();
+        world.set(WanderingClock::default());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::game::Unit;
+    use crate::monster::load_bestiary;
+    use crate::register_components;
+
+    const RAT: &str = r#"[{
+        "name": "Rat",
+        "sprite": { "sheet": "animals", "name": "rat" },
+        "faction": "Animals",
+        "ai": "Aggressive",
+        "health": 1,
+        "stats": { "accuracy": 50, "evasion": 0, "crit_chance": 0 },
+        "speed": 100,
+        "perception": 6,
+        "attack": null,
+        "mana": null,
+        "light": null
+    }]"#;
+
+    fn member(monster: &str, min: i32, max: i32) -> EncounterMember {
+        EncounterMember {
+            monster: monster.into(),
+            min,
+            max,
+        }
+    }
+
+    fn rat_pack(min_depth: i32, max_depth: i32) -> Encounter {
+        Encounter {
+            name: "Rat pack".into(),
+            weight: 1,
+            min_depth,
+            max_depth,
+            members: vec![member("Rat", 3, 3)],
+        }
+    }
+
+    #[test]
+    fn choose_respects_depth() {
+        let table = SpawnTable {
+            encounters: vec![rat_pack(2, 3)],
+            ..Default::default()
+        };
+        let mut rng = Rng::new(1);
+        assert!(table.choose(1, &mut rng).is_none());
+        assert!(table.choose(2, &mut rng).is_some());
+        assert!(table.choose(4, &mut rng).is_none());
+    }
+
+    #[test]
+    fn encounter_spawns_as_group() {
+        let world = World::new();
+        register_components(&world);
+        load_bestiary(&world, RAT).unwrap();
+
+        let mut free: Vec<_> = (0..20).map(|x| Pos::new(x, 0)).collect();
+        let mut rng = Rng::new(1);
+        let rats = spawn_encounter(
+            &world,
+            &rat_pack(1, 1),
+            &mut rng,
+            &mut free,
+            Pos::new(10, 0),
+        );
+        assert_eq!(3, rats.len());
+        assert_eq!(17, free.len());
+        for rat in &rats {
+            let pos = world.entity_from_id(*rat).get::<&Pos>(|p| *p);
+            assert!(pos.distance(Pos::new(10, 0)) <= 1);
+        }
+        let leader = world.entity_from_id(rats[0]);
+        assert!(leader.target::<Follows>(0).is_none());
+        for rat in &rats[1..] {
+            let followed = world.entity_from_id(*rat).target::<Follows>(0).map(|e| *e);
+            assert_eq!(Some(rats[0]), followed);
+        }
+    }
+
+    #[test]
+    fn nothing_spawns_near_start() {
+        let world = World::new();
+        register_components(&world);
+        load_bestiary(&world, RAT).unwrap();
+        world.set(SpawnTable {
+            encounters: vec![rat_pack(1, 5)],
+            base_encounters: 2,
+            encounters_per_depth: 1,
+            min_start_distance: 5,
+            ..Default::default()
+        });
+
+        let free: Vec<_> = (0..30).map(|x| Pos::new(x, 0)).collect();
+        populate_level(&world, 2, free, Pos::new(0, 0));
+
+        let mut count = 0;
+        world.query::<(&Unit, &Pos)>().build().each(|(_, pos)| {
+            assert!(pos.x >= 5);
+            count += 1;
+        });
+        assert_eq!(9, count);
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, DeJsonErr, SerJson};
+
+use crate::game::DamageKind;
+use crate::morgue::Kills;
+use crate::persist::{PersistExtension, PersistModule};
+use crate::util::flecs_extension::KfWorldExtensions;
+
+/// Singleton, everything the player did during the run.
+/// Kills are counted in `Kills`.
+#[derive(Component, Debug, Clone, Default, DeJson, SerJson)]
+pub struct Statistics {
+    pub damage_dealt: HashMap<DamageKind, i32>,
+    pub damage_taken: HashMap<DamageKind, i32>,
+    pub tiles_explored: i32,
+    pub pushes_into_walls: i32,
+    /// names of the achievements already unlocked
+    pub unlocked: Vec<String>,
+}
+
+impl Statistics {
+    pub fn deal(&mut self, kind: DamageKind, amount: i32) {
+        *self.damage_dealt.entry(kind).or_default() += amount;
+    }
+
+    pub fn take(&mut self, kind: DamageKind, amount: i32) {
+        *self.damage_taken.entry(kind).or_default() += amount;
+    }
+
+    /// `None` sums up all kinds
+    pub fn dealt(&self, kind: Option<DamageKind>) -> i32 {
+        sum_kind(&self.damage_dealt, kind)
+    }
+
+    /// `None` sums up all kinds
+    pub fn taken(&self, kind: Option<DamageKind>) -> i32 {
+        sum_kind(&self.damage_taken, kind)
+    }
+
+    pub fn is_unlocked(&self, achievement: &Achievement) -> bool {
+        self.unlocked.contains(&achievement.name)
+    }
+}
+
+fn sum_kind(tally: &HashMap<DamageKind, i32>, kind: Option<DamageKind>) -> i32 {
+    match kind {
+        Some(kind) => tally.get(&kind).copied().unwrap_or(0),
+        None => tally.values().sum(),
+    }
+}
+
+/// What has to be reached to unlock an achievement
+#[derive(Debug, Clone, PartialEq, DeJson, SerJson)]
+pub enum Condition {
+    /// of one kind of monster, or of any if `monster` is None
+    Kills {
+        monster: Option<String>,
+        count: i32,
+    },
+    DamageDealt {
+        kind: Option<DamageKind>,
+        amount: i32,
+    },
+    DamageTaken {
+        kind: Option<DamageKind>,
+        amount: i32,
+    },
+    TilesExplored {
+        count: i32,
+    },
+    PushesIntoWalls {
+        count: i32,
+    },
+}
+
+impl Condition {
+    pub fn met(&self, stats: &Statistics, kills: &Kills) -> bool {
+        match self {
+            Condition::Kills { monster, count } => {
+                let killed = match monster {
+                    Some(name) => kills.by_name.get(name).copied().unwrap_or(0),
+                    None => kills.total(),
+                };
+                killed >= *count
+            }
+            Condition::DamageDealt { kind, amount } => stats.dealt(*kind) >= *amount,
+            Condition::DamageTaken { kind, amount } => stats.taken(*kind) >= *amount,
+            Condition::TilesExplored { count } => stats.tiles_explored >= *count,
+            Condition::PushesIntoWalls { count } => stats.pushes_into_walls >= *count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, DeJson, SerJson)]
+pub struct Achievement {
+    pub name: String,
+    pub description: String,
+    pub condition: Condition,
+}
+
+/// Singleton, all achievements there are, loaded from the data file
+#[derive(Component, Debug, Clone, Default)]
+pub struct AchievementList {
+    pub achievements: Vec<Achievement>,
+}
+
+impl AchievementList {
+    /// Achievements that are met but not unlocked yet
+    pub fn newly_met<'a>(&'a self, stats: &Statistics, kills: &Kills) -> Vec<&'a Achievement> {
+        self.achievements
+            .iter()
+            .filter(|a| !stats.is_unlocked(a) && a.condition.met(stats, kills))
+            .collect()
+    }
+}
+
+pub fn load_achievements(world: &World, json: &str) -> Result<(), DeJsonErr> {
+    let achievements = Vec::<Achievement>::deserialize_json(json)?;
+    world.set(AchievementList { achievements });
+    Ok(())
+}
+
+#[derive(Component)]
+pub struct StatsComponents {}
+
+impl Module for StatsComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<Statistics>().persist();
+        world.set(Statistics::default());
+        world.component_kf::<AchievementList>();
+        world.set(AchievementList::default());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn achievements_test() {
+        let world = World::new();
+        world.import::<StatsComponents>();
+        let json = r#"[
+            {
+                "name": "First Blood",
+                "description": "Kill something.",
+                "condition": {"Kills": {"monster": null, "count": 1}}
+            },
+            {
+                "name": "Goblin Slayer",
+                "description": "Kill 3 goblins.",
+                "condition": {"Kills": {"monster": "Goblin", "count": 3}}
+            },
+            {
+                "name": "Pyromaniac",
+                "description": "Deal 5 fire damage.",
+                "condition": {"DamageDealt": {"kind": "Fire", "amount": 5}}
+            }
+        ]"#;
+        load_achievements(&world, json).unwrap();
+        let list = world.get::<&AchievementList>(|l| l.clone());
+        assert_eq!(3, list.achievements.len());
+
+        let mut stats = Statistics::default();
+        let mut kills = Kills::default();
+        assert!(list.newly_met(&stats, &kills).is_empty());
+
+        kills.add("Orc");
+        stats.deal(DamageKind::Fire, 3);
+        stats.deal(DamageKind::Cutting, 4);
+        let met = list.newly_met(&stats, &kills);
+        assert_eq!(
+            vec!["First Blood"],
+            met.iter().map(|a| a.name.as_str()).collect::<Vec<_>>()
+        );
+        stats.unlocked.push("First Blood".into());
+
+        stats.deal(DamageKind::Fire, 2);
+        assert_eq!(9, stats.dealt(None));
+        let met = list.newly_met(&stats, &kills);
+        assert_eq!(
+            vec!["Pyromaniac"],
+            met.iter().map(|a| a.name.as_str()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn statistics_persist_test() {
+        let mut stats = Statistics::default();
+        stats.take(DamageKind::Poison, 2);
+        stats.take(DamageKind::Poison, 1);
+        stats.tiles_explored = 12;
+        let loaded = Statistics::deserialize_json(&stats.serialize_json()).unwrap();
+        assert_eq!(3, loaded.taken(Some(DamageKind::Poison)));
+        assert_eq!(0, loaded.taken(Some(DamageKind::Fire)));
+        assert_eq!(12, loaded.tiles_explored);
+    }
+}
@@ -0,0 +1,191 @@
+use derive_more::Display;
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+use crate::game::{DamageKind, Origin, Target};
+use crate::persist::{PersistExtension, PersistModule, PersistTagExtension};
+use crate::util::flecs_extension::KfWorldExtensions;
+
+/// Data of a status effect pair
+/// (Burning, Status)
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Status {
+    pub turns: i32,
+    pub stacks: i32,
+}
+
+#[derive(Component, Debug)]
+#[meta]
+/// Relation
+/// (Burning, Status)
+pub struct Burning {}
+
+#[derive(Component, Debug)]
+#[meta]
+/// Relation
+/// (Poisoned, Status)
+pub struct Poisoned {}
+
+#[derive(Component, Debug)]
+#[meta]
+/// Relation
+/// (Stunned, Status)
+pub struct Stunned {}
+
+#[derive(Component, Debug)]
+#[meta]
+/// Relation
+/// (Bleeding, Status)
+pub struct Bleeding {}
+
+/// What happens when a status gets applied to someone who already has it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackRule {
+    /// keep a single stack, take the longer duration
+    Refresh,
+    /// add up the stacks, take the longer duration
+    Intensify,
+}
+
+/// Runtime handle for the status relations, so they can be stored in data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, DeJson, SerJson)]
+pub enum StatusKind {
+    #[display(fmt = "burning")]
+    Burning,
+    #[display(fmt = "poisoned")]
+    Poisoned,
+    #[display(fmt = "stunned")]
+    Stunned,
+    #[display(fmt = "bleeding")]
+    Bleeding,
+}
+
+impl StatusKind {
+    pub const ALL: [StatusKind; 4] = [
+        StatusKind::Burning,
+        StatusKind::Poisoned,
+        StatusKind::Stunned,
+        StatusKind::Bleeding,
+    ];
+
+    pub fn stack_rule(self) -> StackRule {
+        match self {
+            StatusKind::Burning | StatusKind::Stunned => StackRule::Refresh,
+            StatusKind::Poisoned | StatusKind::Bleeding => StackRule::Intensify,
+        }
+    }
+
+    /// Damage dealt to the affected unit every turn
+    pub fn tick_damage(self, status: &Status) -> Option<(DamageKind, i32)> {
+        match self {
+            StatusKind::Burning => Some((DamageKind::Fire, 1)),
+            StatusKind::Poisoned => Some((DamageKind::Poison, status.stacks)),
+            StatusKind::Bleeding => Some((DamageKind::Bleeding, status.stacks)),
+            StatusKind::Stunned => None,
+        }
+    }
+
+    pub fn get(self, e: EntityView) -> Option<Status> {
+        match self {
+            StatusKind::Burning => get_status::<Burning>(e),
+            StatusKind::Poisoned => get_status::<Poisoned>(e),
+            StatusKind::Stunned => get_status::<Stunned>(e),
+            StatusKind::Bleeding => get_status::<Bleeding>(e),
+        }
+    }
+
+    /// Applies the status following its stack rule
+    pub fn apply(self, e: EntityView, turns: i32) {
+        let new = match (self.get(e), self.stack_rule()) {
+            (None, _) => Status { turns, stacks: 1 },
+            (Some(old), StackRule::Refresh) => Status {
+                turns: old.turns.max(turns),
+                stacks: 1,
+            },
+            (Some(old), StackRule::Intensify) => Status {
+                turns: old.turns.max(turns),
+                stacks: old.stacks + 1,
+            },
+        };
+        match self {
+            StatusKind::Burning => e.set_pair::<Burning, _>(new),
+            StatusKind::Poisoned => e.set_pair::<Poisoned, _>(new),
+            StatusKind::Stunned => e.set_pair::<Stunned, _>(new),
+            StatusKind::Bleeding => e.set_pair::<Bleeding, _>(new),
+        };
+    }
+}
+
+fn get_status<S: ComponentId>(e: EntityView) -> Option<Status> {
+    e.try_get::<(&(S, Status),)>(|(status,)| status.clone())
+}
+
+/// Implemented by the relation tags, ties them to their StatusKind
+pub trait StatusEffect: ComponentId {
+    const KIND: StatusKind;
+}
+
+impl StatusEffect for Burning {
+    const KIND: StatusKind = StatusKind::Burning;
+}
+
+impl StatusEffect for Poisoned {
+    const KIND: StatusKind = StatusKind::Poisoned;
+}
+
+impl StatusEffect for Stunned {
+    const KIND: StatusKind = StatusKind::Stunned;
+}
+
+impl StatusEffect for Bleeding {
+    const KIND: StatusKind = StatusKind::Bleeding;
+}
+
+/// Applies a status on hit, lives on weapons
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct OnHit {
+    pub status: StatusKind,
+    pub turns: i32,
+}
+
+#[derive(Component)]
+pub struct StatusEvent {
+    pub kind: StatusKind,
+    pub turns: i32,
+}
+
+impl StatusEvent {
+    pub fn create<'a>(
+        world: &'a World,
+        kind: StatusKind,
+        turns: i32,
+        origin: Entity,
+        targets: &[Entity],
+    ) -> EntityView<'a> {
+        let ev = world
+            .entity()
+            .set(Self { kind, turns })
+            .add_first::<Origin>(origin);
+        for target in targets {
+            ev.add_first::<Target>(*target);
+        }
+        ev
+    }
+}
+
+#[derive(Component)]
+pub struct StatusComponents {}
+
+impl Module for StatusComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<StatusEvent>();
+        world.component_kf::<Status>().meta().persist();
+        world.component_kf::<Burning>().meta().persist();
+        world.component_kf::<Poisoned>().meta().persist();
+        world.component_kf::<Stunned>().meta().persist();
+        world.component_kf::<Bleeding>().meta().persist();
+    }
+}
@@ -0,0 +1,143 @@
+use derive_more::Display;
+use flecs_ecs::prelude::*;
+use nanoserde::{DeJson, SerJson};
+
+use crate::game::Origin;
+use crate::persist::{PersistExtension, PersistModule};
+use crate::util::flecs_extension::KfWorldExtensions;
+use crate::util::pos::Pos;
+
+/// How far the noise of common actions carries, in tiles
+pub const NOISE_STEP: i32 = 2;
+pub const NOISE_ATTACK: i32 = 8;
+pub const NOISE_CAST: i32 = 6;
+pub const NOISE_IMPACT: i32 = 5;
+pub const NOISE_PUSH: i32 = 6;
+
+/// Notice points an unaware unit needs before it becomes alert
+pub const NOTICE_THRESHOLD: i32 = 10;
+/// Notice points gained per turn of seeing an enemy
+pub const NOTICE_SIGHT: i32 = 4;
+
+/// Enum relation, units without one are alert
+#[derive(Component, Display, Debug, Clone, Copy, PartialEq, Eq, DeJson, SerJson)]
+#[meta]
+#[repr(C)]
+pub enum Awareness {
+    /// only noise wakes it up
+    #[display(fmt = "asleep")]
+    Asleep,
+    /// notices enemies by sight and noise
+    #[display(fmt = "unaware")]
+    Unaware,
+    #[display(fmt = "alert")]
+    Alert,
+}
+
+impl Awareness {
+    pub fn of(e: EntityView) -> Self {
+        e.try_get::<&Awareness>(|a| *a).unwrap_or(Awareness::Alert)
+    }
+
+    fn next(self) -> Self {
+        match self {
+            Awareness::Asleep => Awareness::Unaware,
+            Awareness::Unaware | Awareness::Alert => Awareness::Alert,
+        }
+    }
+}
+
+/// Builds up from noise and sight, moves the unit one Awareness step up when full
+#[derive(Component, Debug, Clone, Default, DeJson, SerJson)]
+#[meta]
+pub struct Notice {
+    pub points: i32,
+}
+
+/// In percent, how much less noise and sight the unit gives away
+#[derive(Component, Debug, Clone, DeJson, SerJson)]
+#[meta]
+pub struct Stealth {
+    pub percent: i32,
+}
+
+impl Stealth {
+    pub fn of(e: EntityView) -> i32 {
+        e.try_get::<&Stealth>(|s| s.percent).unwrap_or(0)
+    }
+
+    /// What is left of `amount` after the stealth of `e`
+    pub fn reduce(e: EntityView, amount: i32) -> i32 {
+        amount * (100 - Stealth::of(e)).max(0) / 100
+    }
+}
+
+/// Adds notice points to the unit and raises its awareness if they fill up.
+/// Returns true if the awareness changed.
+pub fn notice(unit: EntityView, points: i32) -> bool {
+    let awareness = Awareness::of(unit);
+    if awareness == Awareness::Alert || points <= 0 {
+        return false;
+    }
+    let total = unit.try_get::<&Notice>(|n| n.points).unwrap_or(0) + points;
+    if total < NOTICE_THRESHOLD {
+        unit.set(Notice { points: total });
+        return false;
+    }
+    unit.set(Notice { points: 0 }).add_enum(awareness.next());
+    true
+}
+
+/// Spreads from `pos` around walls, `loudness` is how many steps it carries
+#[derive(Component)]
+pub struct NoiseEvent {
+    pub pos: Pos,
+    pub loudness: i32,
+}
+
+impl NoiseEvent {
+    pub fn create<'a>(world: &'a World, pos: Pos, loudness: i32, origin: Entity) -> EntityView<'a> {
+        world
+            .entity()
+            .set(Self { pos, loudness })
+            .add_first::<Origin>(origin)
+    }
+}
+
+#[derive(Component)]
+pub struct StealthComponents {}
+
+impl Module for StealthComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+
+        world.component_kf::<NoiseEvent>();
+        world.component_kf::<Awareness>().meta().persist();
+        world.component_kf::<Notice>().meta().persist();
+        world.component_kf::<Stealth>().meta().persist();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn notice_test() {
+        let world = World::new();
+        world.import::<StealthComponents>();
+
+        let sleeper = world.entity().add_enum(Awareness::Asleep);
+        assert!(!notice(sleeper, NOTICE_THRESHOLD - 1));
+        assert_eq!(Awareness::Asleep, Awareness::of(sleeper));
+        assert!(notice(sleeper, 1));
+        assert_eq!(Awareness::Unaware, Awareness::of(sleeper));
+        assert!(notice(sleeper, NOTICE_THRESHOLD));
+        assert_eq!(Awareness::Alert, Awareness::of(sleeper));
+        assert!(!notice(sleeper, NOTICE_THRESHOLD));
+
+        let sneaky = world.entity().set(Stealth { percent: 75 });
+        assert_eq!(2, Stealth::reduce(sneaky, 8));
+        assert_eq!(8, Stealth::reduce(sleeper, 8));
+    }
+}
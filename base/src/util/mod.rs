@@ -1,3 +1,4 @@
 pub mod flecs_extension;
 pub mod pos;
+pub mod rng;
 pub mod vec2f;
@@ -69,6 +69,31 @@ impl Pos {
         let dy = i32::abs(self.y - other.y);
         i32::max(dx, dy)
     }
+
+    /// Bresenham line towards `other`
+    /// Excludes the start, includes the end
+    pub fn line_to(&self, other: Self) -> Vec<Self> {
+        let dx = i32::abs(other.x - self.x);
+        let dy = -i32::abs(other.y - self.y);
+        let sx = (other.x - self.x).signum();
+        let sy = (other.y - self.y).signum();
+        let mut err = dx + dy;
+        let mut current = *self;
+        let mut result = Vec::new();
+        while current != other {
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                current.x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                current.y += sy;
+            }
+            result.push(current);
+        }
+        result
+    }
 }
 
 impl Add<(i32, i32)> for Pos {
@@ -161,3 +186,28 @@ impl Sub<Pos> for Pos {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn line_to_test() {
+        let start = Pos::new(0, 0);
+        assert!(start.line_to(start).is_empty());
+        assert_eq!(
+            vec![
+                Pos::new(1, 1),
+                Pos::new(2, 1),
+                Pos::new(3, 2),
+                Pos::new(4, 2)
+            ],
+            start.line_to(Pos::new(4, 2))
+        );
+        let diagonal = Pos::new(3, 3).line_to(Pos::new(0, 0));
+        assert_eq!(
+            vec![Pos::new(2, 2), Pos::new(1, 1), Pos::new(0, 0)],
+            diagonal
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use flecs_ecs::prelude::Component;
+use nanoserde::{DeJson, SerJson};
+
+/// Small splitmix64 generator.
+/// Its whole state is a single number, so it can be persisted with the world
+/// and continues the exact same sequence after loading.
+#[derive(Component, Debug, Clone, Default, DeJson, SerJson)]
+#[meta]
+pub struct Rng {
+    pub state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Inclusive on both ends
+    pub fn range(&mut self, min: i32, max: i32) -> i32 {
+        if max <= min {
+            return min;
+        }
+        let span = (max - min) as u64 + 1;
+        min + (self.next_u64() % span) as i32
+    }
+
+    /// Rolls a d100, true if it is at most `percent`
+    pub fn chance(&mut self, percent: i32) -> bool {
+        self.range(1, 100) <= percent
+    }
+}
+
+/// Singleton, the random number generators of a run, all derived from one seed.
+/// Every part of the game draws from its own stream, so rolling more dice in
+/// combat does not change which monsters spawn.
+#[derive(Component, Debug, Clone, Default, DeJson, SerJson)]
+pub struct RngStreams {
+    pub seed: u64,
+    /// Monsters, items and torches placed into levels, also loot
+    pub spawns: Rng,
+    pub combat: Rng,
+    /// Decisions of monsters, like which way they go around what is in their way
+    pub ai: Rng,
+}
+
+/// Index of the stream the level seeds come from
+const LEVEL_STREAM: u64 = 1;
+
+/// The first number of a generator seeded with the run seed and the
+/// index of the stream, so close seeds still give unrelated streams
+fn stream(seed: u64, index: u64) -> Rng {
+    Rng::new(Rng::new(seed ^ index.wrapping_mul(0xA24BAED4963EE407)).next_u64())
+}
+
+impl RngStreams {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            spawns: stream(seed, 2),
+            combat: stream(seed, 3),
+            ai: stream(seed, 4),
+        }
+    }
+
+    /// A seed from the clock, for runs that were not given one
+    pub fn random_seed() -> u64 {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Rng::new(nanos).next_u64()
+    }
+
+    /// Seed for generating the given level, always the same within a run,
+    /// so a level that gets generated again has the same layout
+    pub fn level_seed(&self, depth: i32) -> u64 {
+        let mut rng = stream(self.seed, LEVEL_STREAM);
+        rng.state = rng.state.wrapping_add(depth as u64);
+        rng.next_u64()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Rng::new(1234);
+        let mut b = Rng::new(1234);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn range_stays_in_bounds() {
+        let mut rng = Rng::new(5);
+        for _ in 0..1000 {
+            let v = rng.range(-2, 3);
+            assert!((-2..=3).contains(&v));
+        }
+        assert_eq!(7, rng.range(7, 7));
+    }
+
+    #[test]
+    fn streams_are_independent() {
+        let mut a = RngStreams::new(42);
+        let mut b = RngStreams::new(42);
+        // drawing from one stream leaves the others alone
+        for _ in 0..10 {
+            a.combat.next_u64();
+        }
+        assert_eq!(a.spawns.next_u64(), b.spawns.next_u64());
+        assert_ne!(a.combat.next_u64(), b.combat.next_u64());
+        let mut c = RngStreams::new(42);
+        assert_ne!(c.spawns.next_u64(), c.combat.next_u64());
+        assert_ne!(c.combat.next_u64(), c.ai.next_u64());
+
+        assert_eq!(a.level_seed(3), b.level_seed(3));
+        assert_ne!(a.level_seed(3), a.level_seed(4));
+        assert_ne!(a.level_seed(3), RngStreams::new(43).level_seed(3));
+    }
+}
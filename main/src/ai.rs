@@ -0,0 +1,199 @@
+use base::ability::{abilities, cast, Ability};
+use base::combat::{fire_projectile, melee_attack, CombatComponents};
+use base::equipment::ranged_weapon;
+use base::faction::{is_hostile, FactionComponents};
+use base::flecs_ecs;
+use base::flecs_ecs::prelude::*;
+use base::game::{CanSee, GameComponents, GameOver, Health, Player, TurnPassed, Unit};
+use base::message::{MessageCategory, MessageLog, Severity};
+use base::monster::{AiProfile, MonsterComponents, Speed};
+use base::spawn::Follows;
+use base::status::{Status, Stunned};
+use base::stealth::{notice, Awareness, Stealth, StealthComponents, NOTICE_SIGHT};
+use base::util::flecs_extension::QueryExtKf;
+use base::util::pos::{Direction, Pos};
+use base::util::rng::{Rng, RngStreams};
+
+use crate::{TileKind, TileMap, TilemapComponents, Visible};
+
+#[derive(Component)]
+pub struct AiSystems {}
+
+impl Module for AiSystems {
+    fn module(world: &World) {
+        world.import::<GameComponents>();
+        world.import::<CombatComponents>();
+        world.import::<FactionComponents>();
+        world.import::<MonsterComponents>();
+        world.import::<StealthComponents>();
+        world.import::<TilemapComponents>();
+
+        // unaware units slowly notice the enemies they can see, sleeping ones see nothing
+        world
+            .system_named::<(&Unit, &mut MessageLog)>("NoticeBySight")
+            .term_singleton(1)
+            .with::<TurnPassed>()
+            .singleton()
+            .each_entity(|e, (unit, ml)| {
+                if Awareness::of(e) != Awareness::Unaware {
+                    return;
+                }
+                let world = e.world();
+                let Some(pos) = e.try_get::<&Pos>(|p| *p) else {
+                    return;
+                };
+                let mut points = 0;
+                e.each_target::<CanSee>(|other| {
+                    let other = world.entity_from_id(*other);
+                    if !is_hostile(e, other) {
+                        return;
+                    }
+                    let close = other.try_get::<&Pos>(|p| p.distance(pos) <= 2) == Some(true);
+                    let sight = if close {
+                        NOTICE_SIGHT * 2
+                    } else {
+                        NOTICE_SIGHT
+                    };
+                    points += Stealth::reduce(other, sight);
+                });
+                if notice(e, points) && e.has::<Visible>() {
+                    ml.add(
+                        MessageCategory::Status,
+                        format!("{} notices you!", unit.name),
+                    )
+                    .severity(Severity::Warning)
+                    .about(*e);
+                }
+            });
+
+        // units go for the closest enemy they can see: cast at it or shoot it if they can,
+        // otherwise walk up to it and bump it. Their AiProfile changes how eager they are.
+        world
+            .system_named::<(&mut TileMap, &mut RngStreams, &mut Pos, Option<&mut Speed>)>(
+                "MonsterAct",
+            )
+            .term_singleton(0)
+            .term_singleton(1)
+            .with::<Unit>()
+            .without::<Player>()
+            .without::<(Stunned, Status)>()
+            .with::<TurnPassed>()
+            .singleton()
+            .without::<GameOver>()
+            .singleton()
+            .each_entity(|e, (tm, streams, pos, speed)| {
+                if Awareness::of(e) != Awareness::Alert {
+                    return;
+                }
+                let actions = speed.map_or(1, |speed| speed.actions());
+                for _ in 0..actions {
+                    if !act(e, tm, &mut streams.ai, pos) {
+                        break;
+                    }
+                }
+            });
+    }
+}
+
+/// One action of a monster, returns false if it had nothing to do
+fn act(e: EntityView, tm: &mut TileMap, rng: &mut Rng, pos: &mut Pos) -> bool {
+    let world = e.world();
+    let mut seen = Vec::new();
+    e.each_target::<CanSee>(|other| seen.push(other.id()));
+    let enemy = seen
+        .into_iter()
+        .map(|other| world.entity_from_id(other))
+        .filter(|other| is_hostile(e, *other))
+        .filter_map(|other| other.try_get::<&Pos>(|p| (*p, other)))
+        .min_by_key(|(p, _)| p.distance(*pos));
+    let Some((enemy_pos, enemy)) = enemy else {
+        return follow_leader(e, tm, pos);
+    };
+    let profile = AiProfile::of(e);
+    let away = *pos - enemy_pos;
+
+    let badly_hurt = e
+        .try_get::<&Health>(|hp| hp.current * 3 < hp.max)
+        .unwrap_or(false);
+    if profile == AiProfile::Cowardly && badly_hurt && step(e, tm, pos, away) {
+        return true;
+    }
+    let shooter = ranged_weapon(e).is_some() || !abilities(e).is_empty();
+    let close = pos.distance(enemy_pos) <= 2;
+    if profile == AiProfile::Skirmisher && shooter && close && step(e, tm, pos, away) {
+        return true;
+    }
+
+    if pos.distance(enemy_pos) <= 1 {
+        melee_attack(e, enemy);
+        return true;
+    }
+    if tm.line_of_fire(*pos, enemy_pos) {
+        for ability in abilities(e) {
+            let ability = world.entity_from_id(ability);
+            let offensive = ability.get::<&Ability>(|a| a.is_offensive());
+            if offensive && cast(e, ability, enemy_pos).is_ok() {
+                return true;
+            }
+        }
+    }
+    if let Some((_, ranged)) = ranged_weapon(e) {
+        let in_range = pos.distance(enemy_pos) <= ranged.range;
+        if in_range && tm.line_of_fire(*pos, enemy_pos) {
+            fire_projectile(e, enemy_pos);
+            return true;
+        }
+    }
+
+    if profile == AiProfile::Stationary {
+        return false;
+    }
+    let dir = enemy_pos - *pos;
+    step(e, tm, pos, dir) || sidestep(e, tm, rng, pos, dir)
+}
+
+/// Group members without anything to fight catch up with their leader
+fn follow_leader(e: EntityView, tm: &mut TileMap, pos: &mut Pos) -> bool {
+    let Some(leader) = e.target::<Follows>(0) else {
+        return false;
+    };
+    let Some(leader_pos) = leader.try_get::<&Pos>(|p| *p) else {
+        return false;
+    };
+    if AiProfile::of(e) == AiProfile::Stationary || pos.distance(leader_pos) <= 2 {
+        return false;
+    }
+    step(e, tm, pos, leader_pos - *pos)
+}
+
+/// Goes around what blocks the direct way, trying a random side first
+fn sidestep(e: EntityView, tm: &mut TileMap, rng: &mut Rng, pos: &mut Pos, dir: Direction) -> bool {
+    let (x, y) = (dir.x.signum(), dir.y.signum());
+    // the directions turned by 45 degrees both ways
+    let mut sides = [
+        Direction { x: x - y, y: x + y },
+        Direction { x: x + y, y: y - x },
+    ];
+    if rng.chance(50) {
+        sides.swap(0, 1);
+    }
+    sides.into_iter().any(|side| step(e, tm, pos, side))
+}
+
+/// Moves one tile in the general direction, if the tile is free and safe.
+/// Closed doors get opened instead, that uses up the step.
+fn step(e: EntityView, tm: &mut TileMap, pos: &mut Pos, dir: Direction) -> bool {
+    let new_pos = *pos + (dir.x.signum(), dir.y.signum());
+    if new_pos != *pos && tm.terrain.get_opt(new_pos) == Some(&TileKind::DoorClosed) {
+        tm.set_tile(new_pos, TileKind::DoorOpen);
+        return true;
+    }
+    let walkable = tm.is_walkable(new_pos) && !tm[new_pos].is_hazard();
+    if new_pos == *pos || !walkable || tm.units.contains_key(&new_pos) {
+        return false;
+    }
+    tm.units.remove(pos);
+    tm.units.insert(new_pos, *e);
+    *pos = new_pos;
+    true
+}
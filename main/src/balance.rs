@@ -0,0 +1,99 @@
+//! Lets the bot play many seeded runs to compare balance changes by their outcome.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use base::game::{DamageKind, GameOver};
+use base::morgue::Kills;
+use base::nanoserde::{self, DeJson, SerJson};
+use base::stats::Statistics;
+
+use crate::bot::Bot;
+use crate::headless::Simulation;
+use crate::run::GameData;
+
+/// How one run of the bot ended
+#[derive(Debug, Clone, PartialEq, DeJson, SerJson)]
+pub struct RunReport {
+    pub seed: u64,
+    pub survived: bool,
+    /// None while the player lives
+    pub cause: Option<String>,
+    pub depth: i32,
+    pub turns: u32,
+    pub kills: i32,
+    pub damage_taken: HashMap<DamageKind, i32>,
+}
+
+impl RunReport {
+    pub fn collect(sim: &Simulation, seed: u64) -> Self {
+        let world = &sim.world;
+        let cause = world.try_get::<&GameOver>(|over| over.cause.clone());
+        Self {
+            seed,
+            survived: cause.is_none(),
+            cause,
+            depth: sim.depth(),
+            turns: sim.turn(),
+            kills: world.get::<&Kills>(|kills| kills.total()),
+            damage_taken: world.get::<&Statistics>(|stats| stats.damage_taken.clone()),
+        }
+    }
+}
+
+/// Plays `runs` games with consecutive seeds starting at `seed`
+pub fn simulate(
+    data: &GameData,
+    bot: &Bot,
+    seed: u64,
+    runs: u64,
+    max_turns: u32,
+) -> Result<Vec<RunReport>> {
+    (0..runs)
+        .map(|run| {
+            let seed = seed.wrapping_add(run);
+            let sim = Simulation::with_data(data, seed)?;
+            bot.play(&sim, max_turns);
+            Ok(RunReport::collect(&sim, seed))
+        })
+        .collect()
+}
+
+/// One line per run, with a damage column for every kind
+pub fn to_csv(reports: &[RunReport]) -> String {
+    let mut csv = String::from("seed,survived,cause,depth,turns,kills");
+    for kind in DamageKind::ALL {
+        csv += &format!(",taken_{}", kind.to_string().to_lowercase());
+    }
+    csv.push('\n');
+    for r in reports {
+        let cause = r.cause.as_deref().unwrap_or("").replace('"', "\"\"");
+        csv += &format!(
+            "{},{},\"{cause}\",{},{},{}",
+            r.seed, r.survived, r.depth, r.turns, r.kills
+        );
+        for kind in DamageKind::ALL {
+            csv += &format!(",{}", r.damage_taken.get(&kind).copied().unwrap_or(0));
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+pub fn to_json(reports: &[RunReport]) -> String {
+    reports.to_vec().serialize_json()
+}
+
+/// Averages over all runs, for a quick look without a spreadsheet
+pub fn summary(reports: &[RunReport]) -> String {
+    let runs = reports.len().max(1) as f32;
+    let survived = reports.iter().filter(|r| r.survived).count();
+    let mean = |f: fn(&RunReport) -> f32| reports.iter().map(f).sum::<f32>() / runs;
+    format!(
+        "{} runs, {survived} survived, mean depth {:.2}, mean turns {:.1}, mean kills {:.2}",
+        reports.len(),
+        mean(|r| r.depth as f32),
+        mean(|r| r.turns as f32),
+        mean(|r| r.kills as f32),
+    )
+}
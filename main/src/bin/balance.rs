@@ -0,0 +1,42 @@
+//! Lets the bot play a batch of seeded runs and writes one line of results per run.
+//!
+//! `cargo run --release --bin balance -- --seed 1 --runs 100 --format csv --out runs.csv`
+//!
+//! Takes the map generation options of the game as well, like `--algorithm caves`.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Result};
+use flecsirogue::balance::{simulate, summary, to_csv, to_json, RunReport};
+use flecsirogue::bot::Bot;
+use flecsirogue::headless::DEFAULT_ASSETS;
+use flecsirogue::levelgen::MapGenConfig;
+use flecsirogue::run::{arg, GameData};
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let seed: u64 = arg(&args, "--seed").unwrap_or(1);
+    let runs: u64 = arg(&args, "--runs").unwrap_or(20);
+    let turns: u32 = arg(&args, "--turns").unwrap_or(5000);
+    let format: String = arg(&args, "--format").unwrap_or_else(|| "csv".into());
+    let assets: PathBuf = arg(&args, "--assets").unwrap_or_else(|| DEFAULT_ASSETS.into());
+    let out: Option<PathBuf> = arg(&args, "--out");
+
+    let write: fn(&[RunReport]) -> String = match format.as_str() {
+        "csv" => to_csv,
+        "json" => to_json,
+        _ => bail!("unknown format {format}, use csv or json"),
+    };
+
+    let mut data = GameData::read(&assets)?;
+    data.map_gen = MapGenConfig::from_args(&args)?;
+    let reports = simulate(&data, &Bot::default(), seed, runs, turns)?;
+    let output = write(&reports);
+    match out {
+        Some(path) => std::fs::write(path, output)?,
+        None => print!("{output}"),
+    }
+    // stderr, so the summary does not end up in piped results
+    eprintln!("{}", summary(&reports));
+    Ok(())
+}
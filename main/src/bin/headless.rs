@@ -0,0 +1,57 @@
+//! Plays runs without a window, for CI and quick simulations.
+//!
+//! `cargo run --bin headless -- --seed 7 --turns 500 --runs 3 --algorithm caves`
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use base::util::pos::Direction;
+use base::util::rng::{Rng, RngStreams};
+use flecsirogue::headless::{Action, Simulation, DEFAULT_ASSETS};
+use flecsirogue::levelgen::MapGenConfig;
+use flecsirogue::run::{arg, GameData};
+
+const DIRECTIONS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let seed = arg(&args, "--seed").unwrap_or_else(RngStreams::random_seed);
+    let turns: u32 = arg(&args, "--turns").unwrap_or(1000);
+    let runs: u64 = arg(&args, "--runs").unwrap_or(1);
+    let assets: PathBuf = arg(&args, "--assets").unwrap_or_else(|| DEFAULT_ASSETS.into());
+
+    let mut data = GameData::read(&assets)?;
+    data.map_gen = MapGenConfig::from_args(&args)?;
+
+    for run in 0..runs {
+        let seed = seed.wrapping_add(run);
+        let sim = Simulation::with_data(&data, seed)?;
+        // stumbles around at random, which is enough to exercise every system
+        let mut rng = Rng::new(seed);
+        while !sim.is_over() && sim.turn() < turns {
+            let (x, y) = DIRECTIONS[rng.range(0, DIRECTIONS.len() as i32 - 1) as usize];
+            if !sim.act(Action::Move(Direction { x, y })) {
+                sim.act(Action::Wait);
+            }
+        }
+        let hp = sim.player_health();
+        let outcome = if sim.is_over() { "died" } else { "survived" };
+        println!(
+            "seed {seed}: {outcome} at depth {} on turn {} with {}/{} health",
+            sim.depth(),
+            sim.turn(),
+            hp.current,
+            hp.max
+        );
+    }
+    Ok(())
+}
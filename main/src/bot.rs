@@ -0,0 +1,156 @@
+//! A simple player for the headless simulation: explores, fights what it sees,
+//! runs away when badly hurt and takes the stairs down once a level is explored.
+
+use std::collections::{HashMap, VecDeque};
+
+use base::faction::is_hostile;
+use base::flecs_ecs::prelude::*;
+use base::game::{CanSee, Health};
+use base::hunger::{Edible, HungerState};
+use base::util::pos::Pos;
+
+use crate::headless::{Action, Simulation};
+use crate::{TileKind, TileMap, Visibility};
+
+pub struct Bot {
+    /// Share of the maximum health below which the bot runs from enemies
+    pub flee_below: f32,
+    /// Share of the maximum health the bot rests up to before moving on
+    pub rest_until: f32,
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Self {
+            flee_below: 0.3,
+            rest_until: 0.7,
+        }
+    }
+}
+
+impl Bot {
+    /// Picks the next action from what the player knows about the level
+    pub fn decide(&self, sim: &Simulation) -> Action {
+        let world = &sim.world;
+        let player = sim.player();
+        let pos = sim.player_pos();
+        let hp = sim.player_health();
+        let hunger = HungerState::of(player);
+
+        let mut enemies = Vec::new();
+        player.each_target::<CanSee>(|other| {
+            if is_hostile(player, other) {
+                if let Some(other_pos) = other.try_get::<&Pos>(|p| *p) {
+                    enemies.push(other_pos);
+                }
+            }
+        });
+        let mut food = Vec::new();
+        world
+            .query::<(&Edible, &Pos)>()
+            .build()
+            .each(|(_, food_pos)| food.push(*food_pos));
+
+        world.get::<&TileMap>(|tm| {
+            if let Some(enemy) = enemies.iter().copied().min_by_key(|e| e.distance(pos)) {
+                if share(&hp) < self.flee_below {
+                    if let Some(step) = flee(tm, pos, &enemies) {
+                        return Action::Move(step - pos);
+                    }
+                }
+                if pos.distance(enemy) <= 1 {
+                    return Action::Move(enemy - pos);
+                }
+                if let Some(step) = first_step(tm, pos, |p| p == enemy) {
+                    return Action::Move(step - pos);
+                }
+            }
+
+            if hunger >= HungerState::Hungry {
+                if food.contains(&pos) {
+                    return Action::Eat;
+                }
+                let known = |p: Pos| tm.visibility[p] != Visibility::Unseen;
+                let food_known: Vec<Pos> = food.into_iter().filter(|p| known(*p)).collect();
+                if let Some(step) = first_step(tm, pos, |p| food_known.contains(&p)) {
+                    return Action::Move(step - pos);
+                }
+            }
+
+            if hunger.regenerates() && share(&hp) < self.rest_until {
+                return Action::Wait;
+            }
+
+            let frontier = |p: Pos| {
+                p.neighbors()
+                    .iter()
+                    .any(|n| tm.visibility.get_opt(*n) == Some(&Visibility::Unseen))
+            };
+            if let Some(step) = first_step(tm, pos, frontier) {
+                return Action::Move(step - pos);
+            }
+            if tm[pos] == TileKind::StairsDown {
+                return Action::Descend;
+            }
+            if let Some(step) = first_step(tm, pos, |p| tm[p] == TileKind::StairsDown) {
+                return Action::Move(step - pos);
+            }
+            Action::Wait
+        })
+    }
+
+    /// Plays until the player dies or `max_turns` have passed
+    pub fn play(&self, sim: &Simulation, max_turns: u32) {
+        while !sim.is_over() && sim.turn() < max_turns {
+            if !sim.act(self.decide(sim)) {
+                sim.act(Action::Wait);
+            }
+        }
+    }
+}
+
+fn share(hp: &Health) -> f32 {
+    hp.current as f32 / hp.max as f32
+}
+
+/// The free neighbouring tile furthest from all enemies, if it is further than `pos`
+fn flee(tm: &TileMap, pos: Pos, enemies: &[Pos]) -> Option<Pos> {
+    let closest = |p: Pos| enemies.iter().map(|e| e.distance(p)).min().unwrap_or(0);
+    pos.neighbors()
+        .into_iter()
+        .filter(|p| tm.is_walkable(*p) && !tm[*p].is_hazard() && !tm.units.contains_key(p))
+        .max_by_key(|p| closest(*p))
+        .filter(|p| closest(*p) > closest(pos))
+}
+
+/// Closed doors open on bumping into them, hazards are better walked around
+fn passable(tm: &TileMap, pos: Pos) -> bool {
+    let Some(tile) = tm.terrain.get_opt(pos) else {
+        return false;
+    };
+    (tile.is_walkable() || *tile == TileKind::DoorClosed) && !tile.is_hazard()
+}
+
+/// First step on the shortest path over explored tiles to the closest tile
+/// matching `goal`, None if there is no such path or `pos` is a goal itself
+fn first_step(tm: &TileMap, pos: Pos, goal: impl Fn(Pos) -> bool) -> Option<Pos> {
+    let mut came_from = HashMap::from([(pos, pos)]);
+    let mut frontier = VecDeque::from([pos]);
+    while let Some(current) = frontier.pop_front() {
+        if current != pos && goal(current) {
+            let mut step = current;
+            while came_from[&step] != pos {
+                step = came_from[&step];
+            }
+            return Some(step);
+        }
+        for next in current.neighbors() {
+            let known = tm.visibility.get_opt(next) != Some(&Visibility::Unseen);
+            if known && passable(tm, next) && !came_from.contains_key(&next) {
+                came_from.insert(next, current);
+                frontier.push_back(next);
+            }
+        }
+    }
+    None
+}
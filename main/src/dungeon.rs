@@ -0,0 +1,258 @@
+use base::flecs_ecs;
+use base::flecs_ecs::prelude::*;
+use base::game::{Depth, OnLevel, Player};
+use base::message::{MessageCategory, MessageLog};
+use base::nanoserde::{self, DeJson, SerJson};
+use base::persist::{PersistExtension, PersistModule};
+use base::util::flecs_extension::KfWorldExtensions;
+use base::util::pos::Pos;
+use base::vendored::grids::Grid;
+
+use crate::levelgen::level_map;
+use crate::{TileKind, TileMap, TilemapComponents, Visibility};
+
+/// What is left of a level while the player is elsewhere: its terrain,
+/// with the doors as they were left, and how much of it was explored
+#[derive(Debug, Clone, DeJson, SerJson)]
+pub struct LevelMemory {
+    pub depth: i32,
+    pub w: i32,
+    pub h: i32,
+    pub terrain: Vec<TileKind>,
+    pub visibility: Vec<Visibility>,
+}
+
+impl LevelMemory {
+    pub fn of(depth: i32, tm: &TileMap) -> Self {
+        Self {
+            depth,
+            w: tm.w,
+            h: tm.h,
+            terrain: tm.terrain.data.clone(),
+            visibility: tm.visibility.data.clone(),
+        }
+    }
+
+    pub fn into_map(self) -> TileMap {
+        let grid = |data| Grid {
+            data,
+            width: self.w,
+            height: self.h,
+        };
+        TileMap::from_parts(grid(self.terrain), grid(self.visibility))
+    }
+}
+
+/// Singleton, the levels the player left. Saved with the game, so visited
+/// levels are neither generated nor populated again after loading.
+/// Their entities stay in the world, disabled and marked with OnLevel.
+#[derive(Component, Default, DeJson, SerJson)]
+pub struct Dungeon {
+    pub levels: Vec<LevelMemory>,
+}
+
+impl Dungeon {
+    pub fn take(&mut self, depth: i32) -> Option<LevelMemory> {
+        let index = self.levels.iter().position(|l| l.depth == depth)?;
+        Some(self.levels.swap_remove(index))
+    }
+
+    /// Replaces an older memory of the same level
+    pub fn store(&mut self, memory: LevelMemory) {
+        self.take(memory.depth);
+        self.levels.push(memory);
+    }
+}
+
+#[derive(Component)]
+pub struct DungeonComponents {}
+
+impl Module for DungeonComponents {
+    fn module(world: &World) {
+        world.import::<PersistModule>();
+        world.import::<TilemapComponents>();
+
+        world.component_kf::<Dungeon>().persist();
+        world.set(Dungeon::default());
+    }
+}
+
+/// Stores the current level and moves the player onto the stairs of the next one.
+/// Has to run outside of systems.
+/// Returns true if the level is new and still needs to be populated.
+pub fn change_level(world: &World, delta: i32) -> bool {
+    let old = world.get::<&Depth>(|d| d.current);
+    let new = old + delta;
+
+    let mut leaving = Vec::new();
+    world
+        .query::<&Pos>()
+        .without::<Player>()
+        .build()
+        .each_entity(|e, _| leaving.push(*e));
+    for e in leaving {
+        world
+            .entity_from_id(e)
+            .set(OnLevel { depth: old })
+            .add::<flecs::Disabled>();
+    }
+
+    let stored = world.get::<&mut Dungeon>(|d| d.take(new));
+    let fresh = stored.is_none();
+    let next_map = stored.map_or_else(|| level_map(world, new), LevelMemory::into_map);
+    let old_map = world.get::<&mut TileMap>(|tm| std::mem::replace(tm, next_map));
+    world.get::<&mut Dungeon>(|d| d.store(LevelMemory::of(old, &old_map)));
+
+    let mut returning = Vec::new();
+    world
+        .query::<&OnLevel>()
+        .with::<flecs::Disabled>()
+        .build()
+        .each_entity(|e, level| {
+            if level.depth == new {
+                returning.push(*e);
+            }
+        });
+    for e in returning {
+        world
+            .entity_from_id(e)
+            .remove::<OnLevel>()
+            .remove::<flecs::Disabled>();
+    }
+
+    world.set(Depth { current: new });
+    let arrival = if delta > 0 {
+        TileKind::StairsUp
+    } else {
+        TileKind::StairsDown
+    };
+    if let Some(pos) = world.get::<&TileMap>(|tm| tm.find(arrival)) {
+        world
+            .query::<&mut Pos>()
+            .with::<Player>()
+            .build()
+            .each(|p| *p = pos);
+    }
+    let text = if delta > 0 {
+        format!("You descend to depth {new}.")
+    } else {
+        format!("You climb up to depth {new}.")
+    };
+    world.get::<&mut MessageLog>(|ml| {
+        ml.add(MessageCategory::Movement, text);
+    });
+    fresh
+}
+
+/// The current map is no entity, so it goes into the Dungeon before saving
+pub fn remember_current_level(world: &World) {
+    let depth = world.get::<&Depth>(|d| d.current);
+    let memory = world.get::<&TileMap>(|tm| LevelMemory::of(depth, tm));
+    world.get::<&mut Dungeon>(|d| d.store(memory));
+}
+
+/// Brings back the current level as it was saved
+/// and puts away the entities of all the others.
+pub fn restore_after_load(world: &World) {
+    let depth = world.get::<&Depth>(|d| d.current);
+    let saved = world.get::<&mut Dungeon>(|d| d.take(depth));
+    world.set(saved.map_or_else(|| level_map(world, depth), LevelMemory::into_map));
+
+    let mut stored = Vec::new();
+    world
+        .query::<&OnLevel>()
+        .build()
+        .each_entity(|e, _| stored.push(*e));
+    for e in stored {
+        world.entity_from_id(e).add::<flecs::Disabled>();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use base::game::{GameComponents, Unit};
+    use base::persist::{deserialize_world, serialize_world, SerializedEntity};
+
+    use super::*;
+
+    fn dungeon_world() -> World {
+        let world = World::new();
+        world.import::<GameComponents>();
+        world.import::<DungeonComponents>();
+        world.set(level_map(&world, 1));
+        world
+    }
+
+    #[test]
+    fn level_change_test() {
+        let world = dungeon_world();
+
+        let player = world.entity().add::<Player>().set(Pos::new(0, 0));
+        let goblin = world
+            .entity()
+            .set(Unit {
+                name: "Goblin".into(),
+            })
+            .set(Pos::new(1, 1));
+
+        assert!(change_level(&world, 1));
+        assert_eq!(2, world.get::<&Depth>(|d| d.current));
+        assert!(goblin.has::<flecs::Disabled>());
+        let up = world.get::<&TileMap>(|tm| tm.find(TileKind::StairsUp));
+        assert_eq!(up, Some(player.get::<&Pos>(|pos| *pos)));
+
+        // the first level is not generated again
+        assert!(!change_level(&world, -1));
+        assert_eq!(1, world.get::<&Depth>(|d| d.current));
+        assert!(!goblin.has::<flecs::Disabled>());
+        assert!(!goblin.has::<OnLevel>());
+        let down = world.get::<&TileMap>(|tm| tm.find(TileKind::StairsDown));
+        assert_eq!(down, Some(player.get::<&Pos>(|pos| *pos)));
+    }
+
+    #[test]
+    fn save_load_test() {
+        let world = dungeon_world();
+        world.entity().add::<Player>().set(Pos::new(0, 0));
+        world
+            .entity()
+            .set(Unit {
+                name: "Goblin".into(),
+            })
+            .set(Pos::new(1, 1));
+        let door = world
+            .get::<&TileMap>(|tm| tm.find(TileKind::Floor))
+            .unwrap();
+        world.get::<&mut TileMap>(|tm| {
+            tm.set_tile(door, TileKind::DoorOpen);
+            tm.visibility[door] = Visibility::Remembered;
+        });
+        assert!(change_level(&world, 1));
+        let explored = world
+            .get::<&TileMap>(|tm| tm.find(TileKind::StairsUp))
+            .unwrap();
+        world.get::<&mut TileMap>(|tm| tm.visibility[explored] = Visibility::Remembered);
+
+        remember_current_level(&world);
+        let json = serialize_world(&world).serialize_json();
+        let loaded = dungeon_world();
+        let entities = Vec::<SerializedEntity>::deserialize_json(&json).unwrap();
+        deserialize_world(&loaded, &entities);
+        restore_after_load(&loaded);
+
+        assert_eq!(2, loaded.get::<&Depth>(|d| d.current));
+        loaded.get::<&TileMap>(|tm| assert_eq!(Visibility::Remembered, tm.visibility[explored]));
+        // back on a level that was visited before saving, not populated again
+        assert!(!change_level(&loaded, -1));
+        loaded.get::<&TileMap>(|tm| {
+            assert_eq!(TileKind::DoorOpen, tm[door]);
+            assert_eq!(Visibility::Remembered, tm.visibility[door]);
+        });
+        let mut goblins = 0;
+        loaded
+            .query::<&Unit>()
+            .build()
+            .each(|unit| goblins += (unit.name == "Goblin") as i32);
+        assert_eq!(1, goblins);
+    }
+}
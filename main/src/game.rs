@@ -1,15 +1,91 @@
+use base::ability::{
+    ability_ready, cast, Ability, AbilityComponents, AbilityEffect, CastEvent, Cooldown, Mana,
+    TargetShape,
+};
+use base::combat::{
+    apply_hit, roll_attack, AttackEvent, AttackRoll, CombatComponents, CombatStats, Projectile,
+};
+use base::equipment::{armor_value, ranged_weapon, wielded_weapon, EquipmentComponents};
 use base::flecs_ecs;
 use base::flecs_ecs::prelude::*;
-use base::util::flecs_extension::KfWorldExtensions;
-use base::util::pos::Pos;
+use base::hunger::{HungerComponents, HungerState, Satiation, STARVATION_INTERVAL};
+use base::loot::{leave_remains, Decay, LootComponents};
+use base::message::{MessageArchive, MessageCategory, MessageLog, Severity};
+use base::monster::{MonsterComponents, Resistances};
+use base::morgue::{Kills, MorgueComponents};
+use base::progression::{
+    kill_reward, Experience, ProgressionComponents, LEVEL_UP_ACCURACY, LEVEL_UP_HEALTH,
+};
+use base::stats::{AchievementList, Statistics, StatsComponents};
+use base::status::{
+    Bleeding, Burning, Poisoned, Status, StatusComponents, StatusEffect, StatusEvent, Stunned,
+};
+use base::stealth::{
+    notice, Awareness, NoiseEvent, Stealth, StealthComponents, NOISE_ATTACK, NOISE_CAST,
+    NOISE_IMPACT, NOISE_PUSH,
+};
+use base::util::flecs_extension::{short_type_name, KfWorldExtensions};
+use base::util::pos::{Direction, Pos};
+use base::util::rng::RngStreams;
 use base::{game::*, util::flecs_extension::QueryExtKf};
 use flecs::pipeline::PostUpdate;
-use graphic::vendored::egui_macroquad::egui;
 
-use crate::{TileKind, TileMap};
+use crate::{OnEnter, TileMap, Visibility};
 
-#[derive(Component)]
-pub struct EguiEnabled {}
+/// Singleton, present while the player picks a tile to shoot or cast at
+#[derive(Component, Default)]
+pub struct Targeting {
+    pub target: Option<Pos>,
+    /// what gets used on the target, the ranged weapon if None
+    pub ability: Option<Entity>,
+}
+
+impl Targeting {
+    /// None if the player has nothing to aim anymore
+    pub fn range(&self, player: EntityView) -> Option<i32> {
+        match self.ability {
+            Some(ability) => player
+                .world()
+                .entity_from_id(ability)
+                .try_get::<&Ability>(|a| a.range),
+            None => ranged_weapon(player).map(|(_, ranged)| ranged.range),
+        }
+    }
+
+    /// Tiles around the target that get hit as well
+    pub fn radius(&self, player: EntityView) -> i32 {
+        self.ability
+            .and_then(|ability| {
+                player
+                    .world()
+                    .entity_from_id(ability)
+                    .try_get::<&Ability>(|a| match a.shape {
+                        TargetShape::Ball => a.radius,
+                        _ => 0,
+                    })
+            })
+            .unwrap_or(0)
+    }
+}
+
+/// Casts abilities without a target right away, otherwise starts aiming them
+pub fn activate_ability(player: EntityView, ability: EntityView, pos: Pos, ml: &mut MessageLog) {
+    let world = player.world();
+    if let Err(err) = ability_ready(player, ability) {
+        ml.add(MessageCategory::Combat, err.to_string());
+        return;
+    }
+    if ability.get::<&Ability>(|a| a.shape) == TargetShape::Caster {
+        if cast(player, ability, pos).is_ok() {
+            world.add::<TurnPassed>();
+        }
+    } else {
+        world.set(Targeting {
+            target: None,
+            ability: Some(*ability),
+        });
+    }
+}
 
 #[derive(Component)]
 pub struct GameSystems {}
@@ -17,8 +93,203 @@ pub struct GameSystems {}
 impl Module for GameSystems {
     fn module(world: &World) {
         world.import::<GameComponents>();
-        world.component_kf::<EguiEnabled>();
+        world.import::<EquipmentComponents>();
+        world.import::<StatusComponents>();
+        world.import::<CombatComponents>();
+        world.import::<ProgressionComponents>();
+        world.import::<LootComponents>();
+        world.import::<AbilityComponents>();
+        world.import::<MonsterComponents>();
+        world.import::<StealthComponents>();
+        world.import::<HungerComponents>();
+        world.import::<MorgueComponents>();
+        world.import::<StatsComponents>();
         world.component_kf::<TileMap>();
+        world.component_kf::<Targeting>();
+
+        world
+            .system_named::<(&mut RngStreams, &Unit, &Unit, &mut MessageLog)>(
+                "AttackEvent processing",
+            )
+            .kind::<PostUpdate>()
+            .with::<AttackEvent>()
+            .with_first_name::<Origin>("$origin")
+            .with_first_name::<Target>("$target")
+            .term_singleton(0)
+            .term_src(1, "$origin")
+            .term_src(2, "$target")
+            .term_singleton(3)
+            .each_iter(|it, _i, (streams, o_unit, t_unit, ml)| {
+                let origin = it.get_var_by_name("origin");
+                let target = it.get_var_by_name("target");
+                let weapon = wielded_weapon(origin);
+                if let Some(pos) = target.try_get::<&Pos>(|pos| *pos) {
+                    NoiseEvent::create(&it.world(), pos, NOISE_ATTACK, *origin);
+                }
+                let (a_stats, d_stats) = (CombatStats::of(origin), CombatStats::of(target));
+                match roll_attack(&mut streams.combat, &a_stats, &d_stats, &weapon) {
+                    AttackRoll::Miss => {
+                        ml.add(
+                            MessageCategory::Combat,
+                            format!("{} misses {}.", o_unit.name, t_unit.name),
+                        )
+                        .about(*origin)
+                        .about(*target);
+                    }
+                    AttackRoll::Hit { damage, critical } => {
+                        if critical {
+                            ml.add(
+                                MessageCategory::Combat,
+                                format!("{} lands a critical hit on {}!", o_unit.name, t_unit.name),
+                            )
+                            .severity(Severity::Warning)
+                            .about(*origin)
+                            .about(*target);
+                        }
+                        apply_hit(origin, target, &weapon, damage);
+                    }
+                }
+            });
+
+        world
+            .system_named::<(&CastEvent, &Unit, &TileMap, &mut MessageLog)>("CastEvent processing")
+            .kind::<PostUpdate>()
+            .with_first_name::<Origin>("$origin")
+            .term_src(1, "$origin")
+            .term_singleton(2)
+            .term_singleton(3)
+            .each_iter(|it, _i, (ev, o_unit, tm, ml)| {
+                let world = it.world();
+                let origin = it.get_var_by_name("origin");
+                let ability = &ev.ability;
+                ml.add(
+                    MessageCategory::Combat,
+                    format!("{} casts {}.", o_unit.name, ability.name),
+                )
+                .about(*origin);
+                NoiseEvent::create(&world, ev.target, NOISE_CAST, *origin);
+
+                let targets: Vec<Entity> = match ability.shape {
+                    TargetShape::Caster => vec![*origin],
+                    TargetShape::Single => tm.units.get(&ev.target).copied().into_iter().collect(),
+                    TargetShape::Ball => tm
+                        .units
+                        .iter()
+                        .filter(|(pos, _)| pos.distance(ev.target) <= ability.radius)
+                        .map(|(_, e)| *e)
+                        .collect(),
+                };
+                if targets.is_empty() {
+                    return;
+                }
+                for effect in &ability.effects {
+                    match *effect {
+                        AbilityEffect::Damage { kind, amount } => {
+                            DamageEvent::create(&world, kind, amount, *origin, &targets);
+                        }
+                        AbilityEffect::Heal { amount } => {
+                            HealEvent::create(&world, amount, *origin, &targets);
+                        }
+                        AbilityEffect::Status { status, turns } => {
+                            StatusEvent::create(&world, status, turns, *origin, &targets);
+                        }
+                        AbilityEffect::Push { distance } => {
+                            let center = match ability.shape {
+                                TargetShape::Ball => ev.target,
+                                _ => origin.get::<&Pos>(|pos| *pos),
+                            };
+                            for target in &targets {
+                                let t_pos = world.entity_from_id(*target).get::<&Pos>(|pos| *pos);
+                                let dir = t_pos - center;
+                                if dir.x == 0 && dir.y == 0 {
+                                    continue;
+                                }
+                                let dir = Direction {
+                                    x: dir.x.signum(),
+                                    y: dir.y.signum(),
+                                };
+                                PushEvent::create(&world, dir, distance, *origin, &[*target]);
+                            }
+                        }
+                    }
+                }
+            });
+
+        // projectiles fly a few tiles each turn and attack the first unit they reach
+        world
+            .system_named::<(
+                &mut Projectile,
+                &mut Pos,
+                &TileMap,
+                &mut RngStreams,
+                &mut MessageLog,
+            )>("ProjectileFlight")
+            .term_singleton(2)
+            .term_singleton(3)
+            .term_singleton(4)
+            .with::<TurnPassed>()
+            .singleton()
+            .each_entity(|e, (projectile, pos, tm, streams, ml)| {
+                let world = e.world();
+                // the shooter might have died in the meantime, the projectile itself
+                // can not take the blame since it is gone after the hit
+                let shooter = e.target::<Origin>(0).unwrap_or_else(|| environment(&world));
+                for _ in 0..projectile.speed {
+                    let Some(next) = projectile.path.get(projectile.next).copied() else {
+                        break;
+                    };
+                    if tm.blocks_projectiles(next) {
+                        e.destruct();
+                        return;
+                    }
+                    *pos = next;
+                    projectile.next += 1;
+                    let Some(hit) = tm.units.get(&next) else {
+                        continue;
+                    };
+                    let target = world.entity_from_id(*hit);
+                    if *target == *shooter {
+                        continue;
+                    }
+                    let o_name = shooter
+                        .try_get::<&Unit>(|u| u.name.clone())
+                        .unwrap_or_else(|| "Something".into());
+                    let t_name = target
+                        .try_get::<&Unit>(|u| u.name.clone())
+                        .unwrap_or_default();
+                    let (a_stats, d_stats) = (CombatStats::of(shooter), CombatStats::of(target));
+                    let rng = &mut streams.combat;
+                    match roll_attack(rng, &a_stats, &d_stats, &projectile.weapon) {
+                        AttackRoll::Miss => {
+                            ml.add(
+                                MessageCategory::Combat,
+                                format!("{o_name}'s shot misses {t_name}."),
+                            )
+                            .about(shooter)
+                            .about(target);
+                        }
+                        AttackRoll::Hit { damage, critical } => {
+                            if critical {
+                                ml.add(
+                                    MessageCategory::Combat,
+                                    format!("{o_name}'s shot critically hits {t_name}!"),
+                                )
+                                .severity(Severity::Warning)
+                                .about(shooter)
+                                .about(target);
+                            }
+                            apply_hit(shooter, target, &projectile.weapon, damage);
+                        }
+                    }
+                    NoiseEvent::create(&world, next, NOISE_IMPACT, *shooter);
+                    e.destruct();
+                    return;
+                }
+                // fell to the ground without hitting anything
+                if projectile.next >= projectile.path.len() {
+                    e.destruct();
+                }
+            });
 
         world
             .system_named::<(
@@ -27,90 +298,481 @@ impl Module for GameSystems {
                 &mut Health,
                 &Unit,
                 &mut MessageLog,
+                &mut Statistics,
             )>("DamageEvent processing")
             .kind::<PostUpdate>()
             .with_first_name::<DamageKind>("$kind")
+            .with_first_name::<Origin>("$origin")
             .with_first_name::<Target>("$target")
             .term_src(1, "$kind")
             .term_src(2, "$target")
             .term_src(3, "$target")
             .term_singleton(4)
-            .each(|(ev, kind, t_hp, t_unit, ml)| {
+            .term_singleton(5)
+            .each_iter(|it, _i, (ev, kind, t_hp, t_unit, ml, stats)| {
                 //println!("Processing {e:?}");
+                let origin = it.get_var_by_name("origin");
+                let target = it.get_var_by_name("target");
                 let name = &t_unit.name;
-                let amount = &ev.amount;
-                ml.messages
-                    .push(format!("{name} takes {amount} {kind} damage."));
+                target.add_first::<LastDamagedBy>(origin);
+                if Awareness::of(target) != Awareness::Alert {
+                    target.add_enum(Awareness::Alert);
+                }
+                let by = origin
+                    .try_get::<&Unit>(|u| u.name.clone())
+                    .unwrap_or_else(|| "something".into());
                 // TODO not only units should be able to take damage
-                // TODO damage resistance
-                t_hp.current -= ev.amount;
+                let absorbed = match kind {
+                    DamageKind::Cutting | DamageKind::Blunt | DamageKind::Pierce => {
+                        armor_value(target).clamp(0, ev.amount)
+                    }
+                    DamageKind::Fire
+                    | DamageKind::Poison
+                    | DamageKind::Bleeding
+                    | DamageKind::Starvation => 0,
+                };
+                let amount = ev.amount - absorbed;
+                let amount = target
+                    .try_get::<&Resistances>(|r| r.apply(*kind, amount))
+                    .unwrap_or(amount);
+                let text = if absorbed > 0 {
+                    format!("{name} takes {amount} {kind} damage ({absorbed} absorbed).")
+                } else {
+                    format!("{name} takes {amount} {kind} damage.")
+                };
+                let severity = if target.has::<Player>() {
+                    Severity::Danger
+                } else {
+                    Severity::Info
+                };
+                ml.add(MessageCategory::Combat, text)
+                    .severity(severity)
+                    .about(*target);
+                t_hp.current -= amount;
+                if origin.has::<Player>() && origin != target {
+                    stats.deal(*kind, amount);
+                }
+                if target.has::<Player>() {
+                    stats.take(*kind, amount);
+                }
+                target.set(LastDamage {
+                    kind: *kind,
+                    amount,
+                    by,
+                });
             });
 
         world
-            .system_named::<(&PushEvent, &Unit, &mut Pos, &mut MessageLog, &mut TileMap)>(
-                "PushEvent processing",
-            )
+            .system_named::<(
+                &PushEvent,
+                &Unit,
+                &mut Pos,
+                &mut MessageLog,
+                &mut TileMap,
+                &mut Statistics,
+            )>("PushEvent processing")
             .kind::<PostUpdate>()
+            .with_first_name::<Origin>("$origin")
             .with_first_name::<Target>("$target")
             .term_src(1, "$target")
             .term_src(2, "$target")
             .term_singleton(3)
             .term_singleton(4)
-            .each_entity(|e, (ev, t_unit, t_pos, ml, tm)| {
-                println!("Processing {e:?}");
+            .term_singleton(5)
+            .each_iter(|it, _i, (ev, t_unit, t_pos, ml, tm, stats)| {
+                let origin = it.get_var_by_name("origin");
+                let target = it.get_var_by_name("target");
                 let name = &t_unit.name;
 
                 let new_pos = *t_pos + ev.direction * ev.distance;
-                let is_floor = tm.terrain[new_pos] == TileKind::Floor;
+                let walkable = tm.is_walkable(new_pos);
                 let maybe_blocker = tm.units.get(&new_pos);
                 let not_blocked = maybe_blocker.is_none();
-                if is_floor && not_blocked {
-                    ml.messages.push(format!("{name} gets pushed."));
+                if walkable && not_blocked {
+                    ml.add(MessageCategory::Movement, format!("{name} gets pushed."))
+                        .about(*target);
                     *t_pos = new_pos;
+                } else {
+                    // slamming into something is loud
+                    NoiseEvent::create(&it.world(), *t_pos, NOISE_PUSH, *target);
+                }
+                if !walkable && origin.has::<Player>() {
+                    stats.pushes_into_walls += 1;
+                }
+            });
+
+        world
+            .system_named::<(&HealEvent, &mut Health, &Unit, &mut MessageLog)>(
+                "HealEvent processing",
+            )
+            .kind::<PostUpdate>()
+            .with_first_name::<Origin>("$origin")
+            .with_first_name::<Target>("$target")
+            .term_src(1, "$target")
+            .term_src(2, "$target")
+            .term_singleton(3)
+            .each_iter(|it, _i, (ev, t_hp, t_unit, ml)| {
+                let origin = it.get_var_by_name("origin");
+                let target = it.get_var_by_name("target");
+                let amount = ev.amount.min(t_hp.max - t_hp.current).max(0);
+                t_hp.current += amount;
+                // regeneration would flood the log otherwise
+                if origin != target && amount > 0 {
+                    ml.add(
+                        MessageCategory::Status,
+                        format!("{} heals {amount} health.", t_unit.name),
+                    )
+                    .severity(Severity::Good)
+                    .about(*target);
+                }
+            });
+
+        world
+            .system_named::<(&mut Regeneration, &Health)>("Regeneration")
+            .with::<TurnPassed>()
+            .singleton()
+            .each_entity(|e, (regen, hp)| {
+                if hp.current >= hp.max || !HungerState::of(e).regenerates() {
+                    regen.progress = 0;
+                    return;
+                }
+                regen.progress += 1;
+                if regen.progress >= regen.turns_per_hp {
+                    regen.progress = 0;
+                    HealEvent::create(&e.world(), 1, *e, &[*e]);
+                }
+            });
+
+        // every turn makes the stomach a bit emptier, an empty one hurts
+        world
+            .system_named::<(&mut Satiation, &Turn, &mut MessageLog)>("Hunger")
+            .term_singleton(1)
+            .term_singleton(2)
+            .with::<TurnPassed>()
+            .singleton()
+            .each_entity(|e, (satiation, turn, ml)| {
+                let before = satiation.state();
+                satiation.current = (satiation.current - 1).max(0);
+                let after = satiation.state();
+                if after > before {
+                    if let Some(warning) = after.warning() {
+                        ml.add(MessageCategory::Status, warning)
+                            .severity(Severity::Warning)
+                            .about(*e);
+                    }
+                }
+                if after == HungerState::Starving && turn.number % STARVATION_INTERVAL == 0 {
+                    DamageEvent::create(&e.world(), DamageKind::Starvation, 1, *e, &[*e]);
+                }
+            });
+
+        world
+            .system_named::<(&StatusEvent, &Unit, &mut MessageLog)>("StatusEvent processing")
+            .kind::<PostUpdate>()
+            .with_first_name::<Target>("$target")
+            .term_src(1, "$target")
+            .term_singleton(2)
+            .each_iter(|it, _i, (ev, t_unit, ml)| {
+                let target = it.get_var_by_name("target");
+                ev.kind.apply(target, ev.turns);
+                ml.add(
+                    MessageCategory::Status,
+                    format!("{} is {}.", t_unit.name, ev.kind),
+                )
+                .severity(Severity::Warning)
+                .about(*target);
+            });
+
+        status_tick::<Burning>(world);
+        status_tick::<Poisoned>(world);
+        status_tick::<Stunned>(world);
+        status_tick::<Bleeding>(world);
+
+        // noise travels around walls and raises the awareness of everyone it reaches
+        world
+            .system_named::<(&NoiseEvent, &TileMap, &mut MessageLog)>("NoiseEvent processing")
+            .kind::<PostUpdate>()
+            .term_singleton(1)
+            .term_singleton(2)
+            .each_entity(|e, (ev, tm, ml)| {
+                let world = e.world();
+                let origin = e.target::<Origin>(0);
+                for (pos, distance) in tm.noise_reach(ev.pos, ev.loudness) {
+                    let Some(unit) = tm.units.get(&pos) else {
+                        continue;
+                    };
+                    let unit = world.entity_from_id(*unit);
+                    if origin.is_some_and(|o| *o == *unit) {
+                        continue;
+                    }
+                    let points = (ev.loudness - distance) * 2;
+                    let points = origin.map_or(points, |o| Stealth::reduce(o, points));
+                    let before = Awareness::of(unit);
+                    if notice(unit, points) && tm.visibility[pos] == Visibility::Seen {
+                        let name = unit.get::<&Unit>(|u| u.name.clone());
+                        let text = match before {
+                            Awareness::Asleep => format!("{name} wakes up."),
+                            _ => format!("{name} becomes alert."),
+                        };
+                        ml.add(MessageCategory::Status, text).about(*unit);
+                    }
                 }
             });
 
         world
             .system_named::<()>("Event cleanup")
             .kind::<PostUpdate>()
+            .with::<AttackEvent>()
+            .or()
             .with::<DamageEvent>()
             .or()
+            .with::<HealEvent>()
+            .or()
             .with::<PushEvent>()
+            .or()
+            .with::<StatusEvent>()
+            .or()
+            .with::<CastEvent>()
+            .or()
+            .with::<NoiseEvent>()
             .each_entity(|e, _| {
-                println!("Deleting {e:?}");
                 e.destruct();
             });
 
+        // slow steps take their extra turns one per frame, like resting
         world
-            .system_named::<(&mut MessageLog, &Unit, &Health)>("UnitRemoveDead")
+            .system_named::<&mut SlowMove>("SlowMove")
             .term_singleton(0)
-            .each_entity(|entity, (ml, unit, hp)| {
-                if hp.current <= 0 {
-                    ml.messages.push(format!("{} dies.", unit.name));
-                    println!("Deleting an entitiy. {:?}", entity);
-                    entity.destruct();
+            .without::<TurnPassed>()
+            .singleton()
+            .each_iter(|it, _, slow| {
+                let world = it.world();
+                slow.turns -= 1;
+                world.add::<TurnPassed>();
+                if slow.turns <= 0 {
+                    world.remove::<SlowMove>();
                 }
             });
+
+        // the terrain acts on whoever ends a turn on it
         world
-            .system_named::<&MessageLog>("EguiMessageLog")
+            .system_named::<(&TileMap, &Unit, &Pos, &mut MessageLog)>("TerrainEffects")
+            .kind::<PostUpdate>()
             .term_singleton(0)
-            .with::<EguiEnabled>()
+            .term_singleton(3)
+            .with::<TurnPassed>()
             .singleton()
-            .each(|ml| {
-                graphic::egui::Window::new("Message Log").show(egui(), |ui| {
-                    for msg in &ml.messages {
-                        ui.label(msg);
+            .without::<GameOver>()
+            .singleton()
+            .each_entity(|e, (tm, unit, pos, ml)| {
+                let world = e.world();
+                match tm[*pos].props().on_enter {
+                    Some(OnEnter::Burn(amount)) => {
+                        DamageEvent::create(&world, DamageKind::Fire, amount, *e, &[*e]);
                     }
-                });
+                    Some(OnEnter::Fall) if e.has::<Player>() => {
+                        ml.add(MessageCategory::Movement, "You fall down the chasm!")
+                            .severity(Severity::Warning);
+                        world.set(LevelChange { delta: 1 });
+                    }
+                    Some(OnEnter::Fall) => {
+                        ml.add(
+                            MessageCategory::Movement,
+                            format!("{} falls down the chasm.", unit.name),
+                        )
+                        .about(*e);
+                        e.destruct();
+                    }
+                    None => {}
+                }
+            });
+
+        world
+            .system_named::<(&mut Turn, &mut MessageLog)>("TurnEnd")
+            .kind::<PostUpdate>()
+            .term_singleton(0)
+            .term_singleton(1)
+            .with::<TurnPassed>()
+            .singleton()
+            .each_iter(|it, _, (turn, ml)| {
+                turn.number += 1;
+                ml.turn = turn.number;
+                it.world().remove::<TurnPassed>();
+            });
+
+        world
+            .system_named::<(&mut MessageLog, &mut RngStreams, &mut Kills, &Unit, &Health)>(
+                "UnitRemoveDead",
+            )
+            .term_singleton(0)
+            .term_singleton(1)
+            .term_singleton(2)
+            .without::<GameOver>()
+            .singleton()
+            .each_entity(|entity, (ml, streams, kills, unit, hp)| {
+                if hp.current <= 0 {
+                    let severity = if entity.has::<Player>() {
+                        Severity::Danger
+                    } else {
+                        Severity::Good
+                    };
+                    ml.add(MessageCategory::Combat, format!("{} dies.", unit.name))
+                        .severity(severity);
+                    if let Some(killer) = entity.target::<LastDamagedBy>(0) {
+                        if killer != entity && killer.is_alive() {
+                            let reward = kill_reward(entity);
+                            killer.try_get::<&mut Experience>(|exp| exp.xp += reward);
+                            if killer.has::<Player>() {
+                                kills.add(&unit.name);
+                            }
+                        }
+                    }
+                    if entity.has::<Player>() {
+                        // the player sticks around so the rest of the world still has a
+                        // point of view, but nothing acts anymore
+                        let cause = entity
+                            .try_get::<&LastDamage>(|ld| {
+                                format!(
+                                    "Killed by {} with {} {} damage.",
+                                    ld.by, ld.amount, ld.kind
+                                )
+                            })
+                            .unwrap_or_else(|| "Died of unknown causes.".into());
+                        entity.world().set(GameOver { cause });
+                    } else {
+                        leave_remains(entity, &mut streams.spawns);
+                        entity.destruct();
+                    }
+                }
+            });
+
+        world
+            .system_named::<&mut Mana>("ManaRegeneration")
+            .with::<TurnPassed>()
+            .singleton()
+            .each(|mana| {
+                if mana.current >= mana.max {
+                    mana.progress = 0;
+                    return;
+                }
+                mana.progress += 1;
+                if mana.progress >= mana.turns_per_point {
+                    mana.progress = 0;
+                    mana.current += 1;
+                }
+            });
+
+        world
+            .system_named::<&mut Cooldown>("Cooldown")
+            .with::<TurnPassed>()
+            .singleton()
+            .each_entity(|e, cd| {
+                cd.turns -= 1;
+                if cd.turns <= 0 {
+                    e.remove::<Cooldown>();
+                }
+            });
+
+        world
+            .system_named::<&mut Decay>("Decay")
+            .with::<TurnPassed>()
+            .singleton()
+            .each_entity(|e, decay| {
+                decay.turns -= 1;
+                if decay.turns <= 0 {
+                    e.destruct();
+                }
+            });
+
+        world
+            .system_named::<(&mut Experience, &mut Health, &Unit, &mut MessageLog)>("LevelUp")
+            .term_singleton(3)
+            .each_entity(|e, (exp, hp, unit, ml)| {
+                let gained = exp.level_up();
+                if gained > 0 {
+                    ml.add(
+                        MessageCategory::Progression,
+                        format!("{} reaches level {}!", unit.name, exp.level),
+                    )
+                    .severity(Severity::Good)
+                    .about(*e);
+                    hp.max += gained * LEVEL_UP_HEALTH;
+                    hp.current += gained * LEVEL_UP_HEALTH;
+                    let mut stats = CombatStats::of(e);
+                    stats.accuracy += gained * LEVEL_UP_ACCURACY;
+                    e.set(stats);
+                }
+            });
+
+        world
+            .system_named::<(&mut Statistics, &Kills, &AchievementList, &mut MessageLog)>(
+                "Achievements",
+            )
+            .term_singleton(0)
+            .term_singleton(1)
+            .term_singleton(2)
+            .term_singleton(3)
+            .each(|(stats, kills, list, ml)| {
+                for achievement in list.newly_met(stats, kills) {
+                    ml.add(
+                        MessageCategory::Progression,
+                        format!("Achievement unlocked: {}!", achievement.name),
+                    )
+                    .severity(Severity::Good);
+                    stats.unlocked.push(achievement.name.clone());
+                }
+            });
+
+        world
+            .system_named::<(&mut MessageLog, &MessageArchive)>("MessageLogArchive")
+            .term_singleton(0)
+            .term_singleton(1)
+            .each(|(ml, archive)| {
+                if let Err(err) = ml.archive_overflow(archive.path.as_deref()) {
+                    eprintln!("Could not archive messages: {err}");
+                }
             });
     }
 }
 
+/// Every turn a status deals its damage and counts down, removing itself when it runs out.
+fn status_tick<S: StatusEffect>(world: &World) {
+    world
+        .system_named::<(&mut (S, Status), &Unit, &mut MessageLog)>(&format!(
+            "StatusTick:{}",
+            short_type_name::<S>()
+        ))
+        .term_singleton(2)
+        .with::<TurnPassed>()
+        .singleton()
+        .each_entity(|e, (status, unit, ml)| {
+            if let Some((kind, amount)) = S::KIND.tick_damage(status) {
+                DamageEvent::create(&e.world(), kind, amount, *e, &[*e]);
+            }
+            status.turns -= 1;
+            if status.turns <= 0 {
+                ml.add(
+                    MessageCategory::Status,
+                    format!("{} is no longer {}.", unit.name, S::KIND),
+                )
+                .about(*e);
+                e.remove::<(S, Status)>();
+            }
+        });
+}
+
 #[cfg(test)]
 mod test {
+    use base::ability::{grant_ability, CastError};
+    use base::combat::fire_projectile;
+    use base::equipment::{equip, Armor, EquipSlot, Equippable, Item, Ranged, Weapon};
+    use base::loot::{Corpse, ItemTemplate, LootEntry, LootTable};
+    use base::status::StatusKind;
+    use base::util::rng::Rng;
     use base::{game::DamageKind, util::pos::Pos, vendored::grids::Grid};
+    use graphic::macroquad::color::WHITE;
 
-    use crate::Visibility;
+    use crate::{TileKind, Visibility};
 
     use super::*;
 
@@ -141,6 +803,594 @@ mod test {
         assert!(!ev.is_alive());
     }
 
+    #[test]
+    fn statistics_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let player = world
+            .entity_named("player")
+            .add::<Player>()
+            .set(Health {
+                max: 10,
+                current: 10,
+            })
+            .set(Unit {
+                name: "Player".into(),
+            });
+        let enemy = world
+            .entity_named("gobbo")
+            .set(Health { max: 5, current: 5 })
+            .set(Unit {
+                name: "Goblin McGobbo".into(),
+            });
+
+        DamageEvent::create(&world, DamageKind::Fire, 2, *player, &[*enemy]);
+        DamageEvent::create(&world, DamageKind::Pierce, 1, *enemy, &[*player]);
+        DamageEvent::create(&world, DamageKind::Bleeding, 1, *player, &[*player]);
+        world.progress();
+
+        world.get::<&Statistics>(|stats| {
+            assert_eq!(2, stats.dealt(Some(DamageKind::Fire)));
+            assert_eq!(2, stats.dealt(None));
+            assert_eq!(1, stats.taken(Some(DamageKind::Pierce)));
+            assert_eq!(2, stats.taken(None));
+        });
+    }
+
+    #[test]
+    fn armor_reduces_damage_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let player = world.entity_named("player");
+        let mail = world
+            .entity_named("mail")
+            .set(Item {
+                name: "Chain Mail".into(),
+            })
+            .set(Equippable {
+                slot: EquipSlot::Armor,
+            })
+            .set(Armor { reduction: 1 });
+        let enemy = world
+            .entity_named("gobbo")
+            .set(Health { max: 5, current: 5 })
+            .set(Unit {
+                name: "Goblin McGobbo".into(),
+            });
+        equip(enemy, mail);
+
+        DamageEvent::create(&world, DamageKind::Cutting, 2, *player, &[*enemy]);
+        world.progress();
+        assert_eq!(4, enemy.get::<&Health>(|hp| hp.current));
+
+        // armor does not help against fire
+        DamageEvent::create(&world, DamageKind::Fire, 2, *player, &[*enemy]);
+        world.progress();
+        assert_eq!(2, enemy.get::<&Health>(|hp| hp.current));
+    }
+
+    /// Runs a fixed number of attacks with the given seed and returns the damage dealt
+    fn seeded_attacks(seed: u64) -> i32 {
+        let world = World::new();
+        world.import::<GameSystems>();
+        world.set(RngStreams {
+            combat: Rng::new(seed),
+            ..Default::default()
+        });
+
+        let player = world
+            .entity_named("player")
+            .set(Unit {
+                name: "Player".into(),
+            })
+            .set(Pos::new(0, 0));
+        let sword = world
+            .entity_named("sword")
+            .set(Item {
+                name: "Sword".into(),
+            })
+            .set(Equippable {
+                slot: EquipSlot::Weapon,
+            })
+            .set(Weapon {
+                kind: DamageKind::Cutting,
+                min_damage: 1,
+                max_damage: 4,
+                push: 0,
+                on_hit: vec![],
+            });
+        equip(player, sword);
+        let dummy = world
+            .entity_named("dummy")
+            .set(Health {
+                max: 100,
+                current: 100,
+            })
+            .set(Unit {
+                name: "Training Dummy".into(),
+            })
+            .set(Pos::new(1, 0));
+
+        for _ in 0..10 {
+            AttackEvent::create(&world, *player, &[*dummy]);
+            world.progress();
+            world.progress();
+        }
+        100 - dummy.get::<&Health>(|hp| hp.current)
+    }
+
+    #[test]
+    fn attack_roll_test() {
+        // the same seed always rolls the same, 6 of 10 attacks hit with seed 7
+        assert_eq!(23, seeded_attacks(7));
+        assert_eq!(seeded_attacks(7), seeded_attacks(7));
+        assert_ne!(seeded_attacks(7), seeded_attacks(8));
+    }
+
+    #[test]
+    fn kill_experience_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let player = world
+            .entity_named("player")
+            .set(Unit {
+                name: "Player".into(),
+            })
+            .set(Health {
+                max: 10,
+                current: 10,
+            })
+            .set(Experience { level: 1, xp: 8 });
+        let enemy = world
+            .entity_named("gobbo")
+            .set(Health { max: 3, current: 3 })
+            .set(Unit {
+                name: "Goblin McGobbo".into(),
+            });
+
+        DamageEvent::create(&world, DamageKind::Cutting, 5, *player, &[*enemy]);
+        for _ in 0..3 {
+            world.progress();
+        }
+        assert!(!enemy.is_alive());
+        assert_eq!(2, player.get::<&Experience>(|exp| exp.level));
+        assert_eq!(11, player.get::<&Experience>(|exp| exp.xp));
+        assert_eq!(13, player.get::<&Health>(|hp| hp.max));
+        assert_eq!(80, player.get::<&CombatStats>(|s| s.accuracy));
+    }
+
+    #[test]
+    fn player_death_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let gobbo = world.entity_named("gobbo").set(Unit {
+            name: "Goblin McGobbo".into(),
+        });
+        let player = world
+            .entity_named("player")
+            .set(Unit {
+                name: "Player".into(),
+            })
+            .set(Health { max: 2, current: 2 })
+            .add::<Player>();
+
+        DamageEvent::create(&world, DamageKind::Blunt, 3, *gobbo, &[*player]);
+        for _ in 0..3 {
+            world.progress();
+        }
+        assert!(player.is_alive());
+        assert_eq!(
+            "Killed by Goblin McGobbo with 3 Blunt damage.",
+            world.get::<&GameOver>(|go| go.cause.clone())
+        );
+    }
+
+    #[test]
+    fn remains_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let dagger = world
+            .entity_named("dagger")
+            .set(Item {
+                name: "Dagger".into(),
+            })
+            .set(Equippable {
+                slot: EquipSlot::Weapon,
+            });
+        let enemy = world
+            .entity_named("gobbo")
+            .set(Health { max: 3, current: 0 })
+            .set(Unit {
+                name: "Goblin McGobbo".into(),
+            })
+            .set(Pos::new(4, 2))
+            .set(LootTable {
+                entries: vec![LootEntry {
+                    chance: 100,
+                    item: ItemTemplate {
+                        name: "Gold Coin".into(),
+                        slot: None,
+                        weapon: None,
+                        ranged: None,
+                        armor: None,
+                        edible: None,
+                    },
+                }],
+            });
+        equip(enemy, dagger);
+
+        world.progress();
+        assert!(!enemy.is_alive());
+        assert_eq!(Some(Pos::new(4, 2)), dagger.try_get::<&Pos>(|pos| *pos));
+
+        let mut corpses = 0;
+        world.each_entity::<(&Corpse, &Pos)>(|_, (corpse, pos)| {
+            assert_eq!("Goblin McGobbo", corpse.name);
+            assert_eq!(Pos::new(4, 2), *pos);
+            corpses += 1;
+        });
+        assert_eq!(1, corpses);
+        let mut coins = 0;
+        world.each::<&Item>(|item| {
+            if item.name == "Gold Coin" {
+                coins += 1;
+            }
+        });
+        assert_eq!(1, coins);
+    }
+
+    #[test]
+    fn heal_and_regeneration_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let healer = world.entity_named("healer");
+        let player = world
+            .entity_named("player")
+            .set(Health {
+                max: 10,
+                current: 4,
+            })
+            .set(Unit {
+                name: "Player".into(),
+            })
+            .set(Regeneration {
+                turns_per_hp: 2,
+                progress: 0,
+            });
+
+        HealEvent::create(&world, 3, *healer, &[*player]);
+        world.progress();
+        assert_eq!(7, player.get::<&Health>(|hp| hp.current));
+
+        for _ in 0..4 {
+            world.add::<TurnPassed>();
+            world.progress();
+            world.progress();
+        }
+        assert_eq!(9, player.get::<&Health>(|hp| hp.current));
+
+        // never above max
+        HealEvent::create(&world, 5, *healer, &[*player]);
+        world.progress();
+        assert_eq!(10, player.get::<&Health>(|hp| hp.current));
+    }
+
+    #[test]
+    fn hunger_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let player = world
+            .entity_named("player")
+            .set(Health {
+                max: 10,
+                current: 10,
+            })
+            .set(Unit {
+                name: "Player".into(),
+            })
+            .set(Satiation {
+                current: 26,
+                max: 100,
+            });
+        let pass_turns = |turns: u32| {
+            for _ in 0..turns {
+                world.add::<TurnPassed>();
+                world.progress();
+                world.progress();
+            }
+        };
+        let state = || HungerState::of(player);
+
+        pass_turns(1);
+        assert_eq!(25, player.get::<&Satiation>(|s| s.current));
+        assert_eq!(HungerState::Normal, state());
+        pass_turns(1);
+        assert_eq!(HungerState::Hungry, state());
+        let warned = world.get::<&MessageLog>(|ml| {
+            ml.messages
+                .iter()
+                .any(|m| m.text == "You are getting hungry.")
+        });
+        assert!(warned);
+        pass_turns(15);
+        assert_eq!(HungerState::Weak, state());
+        pass_turns(9);
+        assert_eq!(HungerState::Starving, state());
+        assert_eq!(10, player.get::<&Health>(|hp| hp.current));
+
+        // starving hurts every STARVATION_INTERVAL turns, on turns 30 and 40
+        pass_turns(15);
+        assert_eq!(0, player.get::<&Satiation>(|s| s.current));
+        assert_eq!(8, player.get::<&Health>(|hp| hp.current));
+        let starved = player.get::<&LastDamage>(|ld| ld.kind);
+        assert_eq!(DamageKind::Starvation, starved);
+    }
+
+    #[test]
+    fn status_tick_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let player = world.entity_named("player");
+        let enemy = world
+            .entity_named("gobbo")
+            .set(Health { max: 5, current: 5 })
+            .set(Unit {
+                name: "Goblin McGobbo".into(),
+            });
+
+        StatusEvent::create(&world, StatusKind::Poisoned, 2, *player, &[*enemy]);
+        world.progress();
+        StatusEvent::create(&world, StatusKind::Poisoned, 1, *player, &[*enemy]);
+        world.progress();
+        let status = StatusKind::Poisoned.get(enemy).unwrap();
+        assert_eq!(2, status.stacks);
+        assert_eq!(2, status.turns);
+
+        for _ in 0..2 {
+            world.add::<TurnPassed>();
+            world.progress();
+            world.progress();
+        }
+        // two stacks for two turns
+        assert_eq!(1, enemy.get::<&Health>(|hp| hp.current));
+        assert!(StatusKind::Poisoned.get(enemy).is_none());
+    }
+
+    #[test]
+    fn projectile_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+        // hits with the first roll, no critical with the second
+        world.set(RngStreams {
+            combat: Rng::new(1),
+            ..Default::default()
+        });
+
+        let shooter = world
+            .entity_named("shooter")
+            .set(Unit {
+                name: "Archer".into(),
+            })
+            .set(Pos::new(0, 1));
+        let bow = world
+            .entity_named("bow")
+            .set(Item { name: "Bow".into() })
+            .set(Equippable {
+                slot: EquipSlot::Ranged,
+            })
+            .set(Weapon {
+                kind: DamageKind::Pierce,
+                min_damage: 2,
+                max_damage: 2,
+                push: 0,
+                on_hit: vec![],
+            })
+            .set(Ranged { range: 6, speed: 3 });
+        equip(shooter, bow);
+        let target = world
+            .entity_named("target")
+            .set(Health { max: 5, current: 5 })
+            .set(Unit {
+                name: "Training Dummy".into(),
+            })
+            .set(Pos::new(5, 1));
+
+        let mut tm = TileMap {
+            w: 10,
+            h: 3,
+            terrain: Grid::new(10, 3, TileKind::Floor),
+            visibility: Grid::new(10, 3, Visibility::Unseen),
+            units: Default::default(),
+            revision: 0,
+            light: Grid::new(10, 3, WHITE),
+        };
+        tm.units.insert(Pos::new(0, 1), *shooter);
+        tm.units.insert(Pos::new(5, 1), *target);
+        assert!(tm.line_of_fire(Pos::new(0, 1), Pos::new(5, 1)));
+        // the target itself blocks shots at whatever stands behind it
+        assert!(!tm.line_of_fire(Pos::new(0, 1), Pos::new(7, 1)));
+        world.set(tm);
+
+        // out of range
+        assert!(fire_projectile(shooter, Pos::new(7, 1)).is_none());
+        let projectile = world.entity_from_id(fire_projectile(shooter, Pos::new(5, 1)).unwrap());
+
+        world.add::<TurnPassed>();
+        world.progress();
+        world.progress();
+        assert_eq!(Pos::new(3, 1), projectile.get::<&Pos>(|pos| *pos));
+        assert_eq!(5, target.get::<&Health>(|hp| hp.current));
+
+        world.add::<TurnPassed>();
+        world.progress();
+        world.progress();
+        assert!(!projectile.is_alive());
+        assert_eq!(3, target.get::<&Health>(|hp| hp.current));
+        assert_eq!(
+            DamageKind::Pierce,
+            target.get::<&LastDamage>(|last| last.kind)
+        );
+    }
+
+    #[test]
+    fn projectile_of_dead_shooter_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+        // hits with the first roll, no critical with the second
+        world.set(RngStreams {
+            combat: Rng::new(1),
+            ..Default::default()
+        });
+
+        let shooter = world
+            .entity_named("shooter")
+            .set(Unit {
+                name: "Archer".into(),
+            })
+            .set(Pos::new(0, 1));
+        let bow = world
+            .entity_named("bow")
+            .set(Item { name: "Bow".into() })
+            .set(Equippable {
+                slot: EquipSlot::Ranged,
+            })
+            .set(Weapon {
+                kind: DamageKind::Pierce,
+                min_damage: 2,
+                max_damage: 2,
+                push: 1,
+                on_hit: vec![],
+            })
+            .set(Ranged { range: 6, speed: 3 });
+        equip(shooter, bow);
+        let target = world
+            .entity_named("target")
+            .set(Health { max: 5, current: 5 })
+            .set(Unit {
+                name: "Training Dummy".into(),
+            })
+            .set(Pos::new(5, 1));
+
+        let mut tm = TileMap {
+            w: 10,
+            h: 3,
+            terrain: Grid::new(10, 3, TileKind::Floor),
+            visibility: Grid::new(10, 3, Visibility::Unseen),
+            units: Default::default(),
+            revision: 0,
+            light: Grid::new(10, 3, WHITE),
+        };
+        tm.units.insert(Pos::new(5, 1), *target);
+        world.set(tm);
+
+        let projectile = world.entity_from_id(fire_projectile(shooter, Pos::new(5, 1)).unwrap());
+        shooter.destruct();
+
+        world.add::<TurnPassed>();
+        world.progress();
+        world.add::<TurnPassed>();
+        world.progress();
+        world.progress();
+        assert!(!projectile.is_alive());
+        assert_eq!(3, target.get::<&Health>(|hp| hp.current));
+        assert_eq!(
+            Some(*environment(&world)),
+            target.target::<LastDamagedBy>(0).map(|e| *e)
+        );
+    }
+
+    #[test]
+    fn cast_ability_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let wizard = world
+            .entity_named("wizard")
+            .set(Unit {
+                name: "Orc Wizard".into(),
+            })
+            .set(Mana {
+                max: 5,
+                current: 5,
+                turns_per_point: 100,
+                progress: 0,
+            })
+            .set(Pos::new(0, 1));
+        let target = world
+            .entity_named("target")
+            .set(Health {
+                max: 10,
+                current: 10,
+            })
+            .set(Unit {
+                name: "Training Dummy".into(),
+            })
+            .set(Pos::new(3, 1));
+        let mut tm = TileMap {
+            w: 10,
+            h: 3,
+            terrain: Grid::new(10, 3, TileKind::Floor),
+            visibility: Grid::new(10, 3, Visibility::Unseen),
+            units: Default::default(),
+            revision: 0,
+            light: Grid::new(10, 3, WHITE),
+        };
+        tm.units.insert(Pos::new(0, 1), *wizard);
+        tm.units.insert(Pos::new(3, 1), *target);
+        world.set(tm);
+
+        let firebolt = grant_ability(
+            wizard,
+            Ability {
+                name: "Firebolt".into(),
+                shape: TargetShape::Single,
+                range: 5,
+                radius: 0,
+                cost: 3,
+                cooldown: 2,
+                effects: vec![AbilityEffect::Damage {
+                    kind: DamageKind::Fire,
+                    amount: 3,
+                }],
+            },
+        );
+        let firebolt = world.entity_from_id(firebolt);
+        assert_eq!(vec![*firebolt], abilities(wizard));
+
+        assert_eq!(
+            Err(CastError::OutOfRange),
+            cast(wizard, firebolt, Pos::new(9, 1))
+        );
+        assert_eq!(Ok(()), cast(wizard, firebolt, Pos::new(3, 1)));
+        world.progress();
+        world.progress();
+        assert_eq!(7, target.get::<&Health>(|hp| hp.current));
+        assert_eq!(2, wizard.get::<&Mana>(|m| m.current));
+        assert_eq!(
+            Err(CastError::Cooldown(2)),
+            cast(wizard, firebolt, Pos::new(3, 1))
+        );
+
+        for _ in 0..2 {
+            world.add::<TurnPassed>();
+            world.progress();
+            world.progress();
+        }
+        assert!(!firebolt.has::<Cooldown>());
+        assert_eq!(
+            Err(CastError::NotEnoughMana),
+            cast(wizard, firebolt, Pos::new(3, 1))
+        );
+    }
+
     #[test]
     fn push_event_test() {
         let world = World::new();
@@ -152,6 +1402,8 @@ mod test {
             terrain: Grid::new(10, 10, TileKind::Floor),
             visibility: Grid::new(10, 10, Visibility::Unseen),
             units: Default::default(),
+            revision: 0,
+            light: Grid::new(10, 10, WHITE),
         };
 
         tm.terrain[(1, 1)] = TileKind::Wall;
@@ -183,4 +1435,91 @@ mod test {
         assert_eq!(3, enemy2.get::<&Health>(|hp| hp.current));
         assert!(!ev.is_alive());
     }
+
+    #[test]
+    fn noise_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let mut tm = TileMap {
+            w: 10,
+            h: 3,
+            terrain: Grid::new(10, 3, TileKind::Floor),
+            visibility: Grid::new(10, 3, Visibility::Unseen),
+            units: Default::default(),
+            revision: 0,
+            light: Grid::new(10, 3, WHITE),
+        };
+        for y in 0..3 {
+            tm.terrain[(5, y)] = TileKind::Wall;
+        }
+        let sleeper = |name: &str, pos: Pos| {
+            world
+                .entity_named(name)
+                .set(Unit { name: name.into() })
+                .set(pos)
+                .add_enum(Awareness::Asleep)
+        };
+        let near = sleeper("near", Pos::new(3, 1));
+        let behind_wall = sleeper("behind wall", Pos::new(7, 1));
+        let thief = world.entity_named("thief").set(Stealth { percent: 100 });
+        tm.units.insert(Pos::new(3, 1), *near);
+        tm.units.insert(Pos::new(7, 1), *behind_wall);
+        world.set(tm);
+
+        let ev = NoiseEvent::create(&world, Pos::new(1, 1), NOISE_ATTACK, *thief);
+        world.progress();
+        // a perfectly stealthy origin does not wake anyone
+        assert_eq!(Awareness::Asleep, Awareness::of(near));
+        assert!(!ev.is_alive());
+
+        let player = world.entity_named("player");
+        NoiseEvent::create(&world, Pos::new(1, 1), NOISE_ATTACK, *player);
+        world.progress();
+        assert_eq!(Awareness::Unaware, Awareness::of(near));
+        // walls swallow the noise
+        assert_eq!(Awareness::Asleep, Awareness::of(behind_wall));
+    }
+
+    #[test]
+    fn terrain_effects_test() {
+        let world = World::new();
+        world.import::<GameSystems>();
+
+        let mut tm = TileMap {
+            w: 10,
+            h: 3,
+            terrain: Grid::new(10, 3, TileKind::Floor),
+            visibility: Grid::new(10, 3, Visibility::Unseen),
+            units: Default::default(),
+            revision: 0,
+            light: Grid::new(10, 3, WHITE),
+        };
+        tm.terrain[(1, 1)] = TileKind::Lava;
+        tm.terrain[(3, 1)] = TileKind::Chasm;
+        tm.terrain[(5, 1)] = TileKind::Chasm;
+        world.set(tm);
+
+        let unit = |name: &str, pos| {
+            world
+                .entity()
+                .set(Unit { name: name.into() })
+                .set(Health {
+                    max: 10,
+                    current: 10,
+                })
+                .set(pos)
+        };
+        let burnt = unit("Goblin", Pos::new(1, 1));
+        let fallen = unit("Orc", Pos::new(3, 1));
+        let player = unit("Player", Pos::new(5, 1)).add::<Player>();
+
+        world.add::<TurnPassed>();
+        world.progress();
+        world.progress();
+        assert_eq!(6, burnt.get::<&Health>(|hp| hp.current));
+        assert!(!fallen.is_alive());
+        assert_eq!(Some(1), world.try_get::<&LevelChange>(|c| c.delta));
+        assert!(player.is_alive());
+    }
 }
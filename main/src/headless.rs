@@ -0,0 +1,144 @@
+//! Runs the game without a window: only the game logic gets imported and
+//! the player acts through [`Simulation::act`] instead of the keyboard.
+
+use std::path::Path;
+
+use anyhow::Result;
+use base::flecs_ecs::prelude::*;
+use base::game::{Depth, GameOver, Health, Player, SlowMove, Turn, TurnPassed};
+use base::message::{MessageArchive, MessageLog};
+use base::register_components;
+use base::util::pos::{Direction, Pos};
+use base::util::rng::RngStreams;
+
+use crate::ai::AiSystems;
+use crate::dungeon::DungeonComponents;
+use crate::game::GameSystems;
+use crate::input::{eat_here, player_step, use_stairs};
+use crate::run::{handle_level_change, start_run, GameData};
+use crate::spawn::SpawnSystems;
+use crate::{TileKind, TileMap, TilemapComponents, TilemapSystems};
+
+/// The assets directory of the repository, for tools run through cargo
+pub const DEFAULT_ASSETS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets");
+
+/// Frames run after every action
+const SETTLE_FRAMES: usize = 3;
+
+/// Everything the player can do without a keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Bumping into an enemy attacks it
+    Move(Direction),
+    Wait,
+    Eat,
+    Descend,
+    Ascend,
+}
+
+pub struct Simulation {
+    pub world: World,
+}
+
+impl Simulation {
+    /// Starts a new run with the data files in `assets`
+    pub fn new(assets: &Path, seed: u64) -> Result<Self> {
+        let data = GameData::read(assets)?;
+        Self::with_data(&data, seed)
+    }
+
+    pub fn with_data(data: &GameData, seed: u64) -> Result<Self> {
+        let world = World::new();
+        register_components(&world);
+        world.set(RngStreams::new(seed));
+        // simulations are run in bulk, their messages are not worth keeping
+        world.set(MessageArchive { path: None });
+        data.load(&world)?;
+
+        world.import::<TilemapComponents>();
+        world.import::<DungeonComponents>();
+        world.import::<GameSystems>();
+        world.import::<AiSystems>();
+        world.import::<SpawnSystems>();
+        world.import::<TilemapSystems>();
+
+        start_run(&world);
+        // lets every unit find its place on the map and look around once
+        world.progress();
+        Ok(Self { world })
+    }
+
+    pub fn player(&self) -> EntityView {
+        let mut player = None;
+        self.world
+            .query::<()>()
+            .with::<Player>()
+            .build()
+            .each_entity(|e, _| player = Some(e.id()));
+        self.world
+            .entity_from_id(player.expect("a run without a player"))
+    }
+
+    pub fn turn(&self) -> u32 {
+        self.world.get::<&Turn>(|t| t.number)
+    }
+
+    pub fn depth(&self) -> i32 {
+        self.world.get::<&Depth>(|d| d.current)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.world.has::<GameOver>()
+    }
+
+    pub fn player_pos(&self) -> Pos {
+        self.player().get::<&Pos>(|pos| *pos)
+    }
+
+    pub fn player_health(&self) -> Health {
+        self.player().get::<&Health>(|hp| hp.clone())
+    }
+
+    /// Lets the player act and the rest of the world react to it.
+    /// Returns false if the action was not possible, then no turn passes.
+    pub fn act(&self, action: Action) -> bool {
+        if self.is_over() {
+            return false;
+        }
+        let world = &self.world;
+        let player = self.player();
+        let done = world.get::<&mut TileMap>(|tm| {
+            world.get::<&mut MessageLog>(|ml| {
+                player.get::<&mut Pos>(|pos| match action {
+                    Action::Move(dir) => player_step(player, tm, ml, pos, *pos + dir),
+                    Action::Wait => {
+                        world.add::<TurnPassed>();
+                        true
+                    }
+                    Action::Eat => {
+                        eat_here(player, *pos, ml);
+                        world.has::<TurnPassed>()
+                    }
+                    Action::Descend => use_stairs(player, tm, ml, *pos, TileKind::StairsDown, 1),
+                    Action::Ascend => use_stairs(player, tm, ml, *pos, TileKind::StairsUp, -1),
+                })
+            })
+        });
+        if !done {
+            return false;
+        }
+        handle_level_change(world);
+        // events created while reacting get processed a frame later, in the game
+        // there are plenty of those between two key presses
+        for _ in 0..SETTLE_FRAMES {
+            world.progress();
+        }
+        // slow steps take a frame for every extra turn
+        while world.has::<SlowMove>() && !self.is_over() {
+            world.progress();
+        }
+        // falling down a chasm happens while settling
+        handle_level_change(world);
+        true
+    }
+}
@@ -2,10 +2,141 @@ use base::flecs_ecs;
 use base::flecs_ecs::prelude::*;
 use graphic::macroquad::prelude::*;
 
-use base::game::{DamageEvent, DamageKind, MessageLog, Player, PushEvent};
-use base::util::{flecs_extension::QueryExtKf, pos::Pos};
+use base::ability::{abilities, cast};
+use base::combat::{fire_projectile, melee_attack};
+use base::equipment::{ranged_weapon, Item};
+use base::faction::{attitude, is_hostile, Attitude};
+use base::game::{
+    Depth, GameOver, Health, LevelChange, Player, Resting, SlowMove, TurnPassed, Unit,
+};
+use base::hunger::{eat, Edible, HungerState};
+use base::loot::Corpse;
+use base::message::{MessageCategory, MessageLog, Severity};
+use base::status::StatusKind;
+use base::stealth::{NoiseEvent, NOISE_STEP};
+use base::util::flecs_extension::{KfWorldExtensions, QueryExtKf};
+use base::util::{pos::Pos, vec2f::Vec2f};
+use flecs::pipeline::OnStore;
 
-use crate::{TileKind, TileMap};
+use crate::camera::CameraWrapper;
+use crate::game::{activate_ability, Targeting};
+use crate::{TileKind, TileMap, Visibility};
+
+/// Takes the stairs of the given kind if the player stands on them.
+/// The level change itself happens outside of the systems.
+pub fn use_stairs(
+    player: EntityView,
+    tm: &TileMap,
+    ml: &mut MessageLog,
+    pos: Pos,
+    kind: TileKind,
+    delta: i32,
+) -> bool {
+    let world = player.world();
+    let depth = world.get::<&Depth>(|d| d.current);
+    if tm.terrain[pos] != kind {
+        ml.add(MessageCategory::Movement, "There are no stairs here.");
+    } else if depth + delta < 1 {
+        ml.add(MessageCategory::Movement, "The way out is sealed.");
+    } else {
+        world.set(LevelChange { delta });
+        return true;
+    }
+    false
+}
+
+/// Walks the player onto the neighbouring `new_pos`. Bumping into an enemy attacks it,
+/// bumping into a friend swaps places and bumping into a closed door opens it.
+/// Returns false if a wall, a locked door or a neutral unit was in the way.
+pub fn player_step(
+    player: EntityView,
+    tm: &mut TileMap,
+    ml: &mut MessageLog,
+    pos: &mut Pos,
+    new_pos: Pos,
+) -> bool {
+    let world = player.world();
+    if StatusKind::Stunned.get(player).is_some() {
+        ml.add(MessageCategory::Status, "You are stunned.")
+            .severity(Severity::Warning);
+        world.add::<TurnPassed>();
+        return true;
+    }
+    // check that we do not hit ourselves
+    let walkable = tm.is_walkable(new_pos);
+    let maybe_blocker = tm.units.get(&new_pos);
+    if let Some(other_entity) = maybe_blocker {
+        let other_ev = world.entity_from_id(*other_entity);
+        let name = other_ev
+            .try_get::<&Unit>(|u| u.name.clone())
+            .unwrap_or_default();
+        match attitude(player, other_ev) {
+            Attitude::Hostile => melee_attack(player, other_ev),
+            Attitude::Friendly => {
+                ml.add(
+                    MessageCategory::Movement,
+                    format!("You swap places with {name}."),
+                )
+                .about(other_ev);
+                other_ev.set(*pos);
+                *pos = new_pos;
+            }
+            Attitude::Neutral => {
+                ml.add(MessageCategory::Movement, format!("{name} is in the way."))
+                    .about(other_ev);
+                return false;
+            }
+        }
+    } else if walkable {
+        *pos = new_pos;
+        NoiseEvent::create(&world, new_pos, NOISE_STEP, *player);
+        let cost = tm[new_pos].props().move_cost;
+        if cost > 1 {
+            world.set(SlowMove { turns: cost - 1 });
+        }
+    } else {
+        match tm.terrain.get_opt(new_pos) {
+            Some(TileKind::DoorClosed) => {
+                tm.set_tile(new_pos, TileKind::DoorOpen);
+                ml.add(MessageCategory::Movement, "You open the door.");
+            }
+            Some(TileKind::DoorLocked) => {
+                ml.add(MessageCategory::Movement, "The door is locked.");
+                return false;
+            }
+            _ => return false,
+        }
+    }
+    world.add::<TurnPassed>();
+    true
+}
+
+/// Eats the first edible thing lying on `pos`, that takes a turn
+pub fn eat_here(player: EntityView, pos: Pos, ml: &mut MessageLog) {
+    let world = player.world();
+    let mut food = None;
+    world
+        .query::<(&Edible, &Pos)>()
+        .build()
+        .each_entity(|e, (_, food_pos)| {
+            if *food_pos == pos && food.is_none() {
+                food = Some(*e);
+            }
+        });
+    let Some(food) = food.map(|e| world.entity_from_id(e)) else {
+        ml.add(MessageCategory::System, "There is nothing to eat here.");
+        return;
+    };
+    let name = food
+        .try_get::<&Item>(|i| i.name.clone())
+        .or_else(|| food.try_get::<&Corpse>(|c| format!("{} corpse", c.name)))
+        .unwrap_or_else(|| "food".into());
+    if eat(player, food).is_some() {
+        ml.add(MessageCategory::Status, format!("You eat the {name}."))
+            .severity(Severity::Good);
+        world.add::<TurnPassed>();
+    }
+}
 
 #[derive(Component)]
 pub struct InputSystems {}
@@ -14,18 +145,35 @@ impl Module for InputSystems {
     fn module(world: &World) {
         // move player
         world
-            .system_named::<(&TileMap, &mut MessageLog, &mut Pos)>("PlayerMovement")
+            .system_named::<(&mut TileMap, &mut MessageLog, &mut Pos)>("PlayerMovement")
             .term_singleton(0)
             .term_singleton(1)
             .with::<Player>()
-            .each_entity(|player_ev, (tm, _ml, pos)| {
-                if !(is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift)) {
+            .without::<GameOver>()
+            .singleton()
+            .without::<Targeting>()
+            .singleton()
+            .without::<SlowMove>()
+            .singleton()
+            .each_entity(|player_ev, (tm, ml, pos)| {
+                let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+                // '>' and '<'
+                let stairs = if is_key_pressed(KeyCode::Period) {
+                    Some((TileKind::StairsDown, 1))
+                } else if is_key_pressed(KeyCode::Comma) {
+                    Some((TileKind::StairsUp, -1))
+                } else {
+                    None
+                };
+                if let Some((kind, delta)) = stairs.filter(|_| shift) {
+                    use_stairs(player_ev, tm, ml, *pos, kind, delta);
+                }
+                if !shift {
                     let direction_keys = [
                         (KeyCode::Kp1, (-1, 1)),
                         (KeyCode::Kp2, (0, 1)),
                         (KeyCode::Kp3, (1, 1)),
                         (KeyCode::Kp4, (-1, 0)),
-                        (KeyCode::Kp5, (0, 0)),
                         (KeyCode::Kp6, (1, 0)),
                         (KeyCode::Kp7, (-1, -1)),
                         (KeyCode::Kp8, (0, -1)),
@@ -38,36 +186,250 @@ impl Module for InputSystems {
                         }
                     }
 
-                    if new_pos != *pos {
-                        // check that we do not hit ourselves
-                        let is_floor = tm.terrain[new_pos] == TileKind::Floor;
-                        let maybe_blocker = tm.units.get(&new_pos);
-                        let not_blocked = maybe_blocker.is_none();
-                        if is_floor && not_blocked {
-                            *pos = new_pos;
-                        }
-                        if let Some(other_entity) = maybe_blocker {
-                            DamageEvent::create(
-                                &player_ev.world(),
-                                DamageKind::Cutting,
-                                2,
-                                *player_ev,
-                                &[*other_entity],
-                            );
-                            let world = player_ev.world();
-                            let other_ev = world.entity_from_id(*other_entity);
-                            let p_pos = player_ev.get::<&Pos>(|pos| *pos);
-                            let t_pos = other_ev.get::<&Pos>(|pos| *pos);
-                            PushEvent::create(
-                                &world,
-                                t_pos - p_pos,
-                                1,
-                                *player_ev,
-                                &[*other_entity],
+                    let ability_keys = [
+                        KeyCode::Key1,
+                        KeyCode::Key2,
+                        KeyCode::Key3,
+                        KeyCode::Key4,
+                        KeyCode::Key5,
+                        KeyCode::Key6,
+                        KeyCode::Key7,
+                        KeyCode::Key8,
+                        KeyCode::Key9,
+                    ];
+                    let pressed_ability = ability_keys
+                        .iter()
+                        .position(|key| is_key_pressed(*key))
+                        .and_then(|i| abilities(player_ev).get(i).copied());
+
+                    if let Some(ability) = pressed_ability {
+                        let ability = player_ev.world().entity_from_id(ability);
+                        activate_ability(player_ev, ability, *pos, ml);
+                    } else if is_key_pressed(KeyCode::Kp5) {
+                        // wait a turn
+                        player_ev.world().add::<TurnPassed>();
+                    } else if is_key_pressed(KeyCode::F) {
+                        if ranged_weapon(player_ev).is_some() {
+                            player_ev.world().set(Targeting::default());
+                            ml.add(
+                                MessageCategory::System,
+                                "Pick a target, Tab for the nearest enemy, Escape to cancel.",
                             );
+                        } else {
+                            ml.add(MessageCategory::System, "You have nothing to shoot with.");
+                        }
+                    } else if is_key_pressed(KeyCode::E) {
+                        eat_here(player_ev, *pos, ml);
+                    } else if is_key_pressed(KeyCode::R) {
+                        let hp = player_ev.get::<&Health>(|hp| hp.current);
+                        player_ev.world().set(Resting { last_hp: hp });
+                        ml.add(MessageCategory::System, "You start resting.");
+                    } else if new_pos != *pos {
+                        player_step(player_ev, tm, ml, pos, new_pos);
+                    }
+                }
+            });
+
+        // aim with the mouse or Tab, shoot or cast with a click or Enter
+        world
+            .system_named::<(
+                &CameraWrapper,
+                &TileMap,
+                &mut Targeting,
+                &mut MessageLog,
+                &Pos,
+            )>("PlayerTargeting")
+            .term_singleton(0)
+            .term_singleton(1)
+            .term_singleton(2)
+            .term_singleton(3)
+            .with::<Player>()
+            .without::<GameOver>()
+            .singleton()
+            .each_entity(|player_ev, (camera, tm, targeting, ml, pos)| {
+                let world = player_ev.world();
+                let Some(range) = targeting.range(player_ev) else {
+                    world.remove::<Targeting>();
+                    return;
+                };
+                if is_key_pressed(KeyCode::Escape) {
+                    world.remove::<Targeting>();
+                    return;
+                }
+                if mouse_delta_position() != Vec2::ZERO || targeting.target.is_none() {
+                    let mp = camera.screen_to_world(Vec2f::from(mouse_position()));
+                    let hovered =
+                        Pos::new((mp.x / 32.).floor() as i32, (mp.y / 32.).floor() as i32);
+                    targeting.target = Some(hovered);
+                }
+                if is_key_pressed(KeyCode::Tab) {
+                    targeting.target = tm
+                        .units
+                        .iter()
+                        .filter(|(p, e)| {
+                            tm.visibility[**p] == Visibility::Seen
+                                && is_hostile(player_ev, world.entity_from_id(**e))
+                        })
+                        .map(|(p, _)| *p)
+                        .min_by_key(|p| p.distance(*pos))
+                        .or(targeting.target);
+                }
+
+                let fire = is_mouse_button_pressed(MouseButton::Left)
+                    || is_key_pressed(KeyCode::Enter)
+                    || is_key_pressed(KeyCode::KpEnter);
+                let Some(target) = targeting.target.filter(|_| fire) else {
+                    return;
+                };
+                if target == *pos {
+                    return;
+                }
+                if pos.distance(target) > range {
+                    ml.add(MessageCategory::Combat, "That is out of range.");
+                    return;
+                }
+                if !tm.line_of_fire(*pos, target) {
+                    ml.add(MessageCategory::Combat, "There is no clear line of fire.");
+                    return;
+                }
+                let used = match targeting.ability {
+                    Some(ability) => match cast(player_ev, world.entity_from_id(ability), target) {
+                        Ok(()) => true,
+                        Err(err) => {
+                            ml.add(MessageCategory::Combat, err.to_string());
+                            false
                         }
+                    },
+                    None => {
+                        let shot = fire_projectile(player_ev, target).is_some();
+                        if shot {
+                            ml.add(MessageCategory::Combat, "You shoot.");
+                        }
+                        shot
                     }
+                };
+                if used {
+                    world.remove::<Targeting>();
+                    world.add::<TurnPassed>();
+                }
+            });
+
+        world
+            .system_named::<(&Targeting, &TileMap, &Pos)>("DrawTargeting")
+            .term_singleton(0)
+            .term_singleton(1)
+            .with::<Player>()
+            .kind::<OnStore>()
+            .each_entity(|player_ev, (targeting, tm, pos)| {
+                let Some(target) = targeting.target else {
+                    return;
+                };
+                let in_range = targeting
+                    .range(player_ev)
+                    .is_some_and(|range| pos.distance(target) <= range);
+                let color = if in_range && tm.line_of_fire(*pos, target) {
+                    Color::new(0., 1., 0., 0.3)
+                } else {
+                    Color::new(1., 0., 0., 0.3)
+                };
+                for p in pos.line_to(target) {
+                    draw_rectangle(p.x as f32 * 32., p.y as f32 * 32., 32., 32., color);
+                }
+                let radius = targeting.radius(player_ev);
+                for p in target.circle_around(radius as u32) {
+                    let area = Color::new(1., 0.5, 0., 0.3);
+                    draw_rectangle(p.x as f32 * 32., p.y as f32 * 32., 32., 32., area);
                 }
             });
+
+        // rest until healed or interrupted, one turn per frame
+        world
+            .system_named::<(&TileMap, &mut MessageLog, &mut Resting, &Health)>("PlayerRest")
+            .term_singleton(0)
+            .term_singleton(1)
+            .term_singleton(2)
+            .with::<Player>()
+            .without::<GameOver>()
+            .singleton()
+            .without::<TurnPassed>()
+            .singleton()
+            .each_entity(|player_ev, (tm, ml, rest, hp)| {
+                let world = player_ev.world();
+                let hostile_in_sight = tm.units.iter().any(|(pos, e)| {
+                    tm.visibility[*pos] == Visibility::Seen
+                        && is_hostile(player_ev, world.entity_from_id(*e))
+                });
+                let stop_reason = if hp.current >= hp.max {
+                    Some("You feel rested.")
+                } else if !HungerState::of(player_ev).regenerates() {
+                    Some("You are too hungry to rest.")
+                } else if hostile_in_sight {
+                    Some("You stop resting, something is nearby.")
+                } else if hp.current < rest.last_hp {
+                    Some("Your rest gets interrupted.")
+                } else {
+                    None
+                };
+                if let Some(reason) = stop_reason {
+                    ml.add(MessageCategory::System, reason);
+                    world.remove::<Resting>();
+                } else {
+                    rest.last_hp = hp.current;
+                    world.add::<TurnPassed>();
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use base::faction::Faction;
+    use base::vendored::grids::Grid;
+
+    use super::*;
+
+    #[test]
+    fn swap_places_test() {
+        let world = World::new();
+        base::register_components(&world);
+        let unit = |name: &str, faction: Faction, pos: Pos| {
+            world
+                .entity_named(name)
+                .set(Unit { name: name.into() })
+                .add_enum(faction)
+                .set(pos)
+        };
+        let player = unit("player", Faction::Player, Pos::new(1, 0)).add::<Player>();
+        let dog = unit("dog", Faction::Player, Pos::new(0, 0));
+        let merchant = unit("merchant", Faction::Neutral, Pos::new(2, 0));
+        let mut tm = TileMap::from_parts(
+            Grid::new(3, 1, TileKind::Floor),
+            Grid::new(3, 1, Visibility::Unseen),
+        );
+        tm.units.insert(Pos::new(0, 0), *dog);
+        tm.units.insert(Pos::new(2, 0), *merchant);
+        let mut ml = MessageLog::default();
+        let mut pos = Pos::new(1, 0);
+
+        // neutral units are in the way
+        assert!(!player_step(
+            player,
+            &mut tm,
+            &mut ml,
+            &mut pos,
+            Pos::new(2, 0)
+        ));
+        assert_eq!(Pos::new(1, 0), pos);
+        assert_eq!(Pos::new(2, 0), merchant.get::<&Pos>(|p| *p));
+
+        assert!(player_step(
+            player,
+            &mut tm,
+            &mut ml,
+            &mut pos,
+            Pos::new(0, 0)
+        ));
+        assert_eq!(Pos::new(0, 0), pos);
+        assert_eq!(Pos::new(1, 0), dog.get::<&Pos>(|p| *p));
     }
 }
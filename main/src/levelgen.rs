@@ -0,0 +1,313 @@
+//! Which map generation pipeline builds which level. The pipelines are put together
+//! from the filters of the mapgen crate and configured per depth in the data files.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Result};
+use base::flecs_ecs;
+use base::flecs_ecs::prelude::*;
+use base::nanoserde::{self, DeJson, SerJson};
+use base::util::flecs_extension::KfWorldExtensions;
+use base::util::rng::{Rng, RngStreams};
+use mapgen::*;
+
+use crate::run::arg;
+use crate::TileMap;
+
+pub const MIN_MAP_SIZE: i32 = 20;
+pub const MAX_MAP_SIZE: i32 = 120;
+/// Maps with less floor than this share get rejected
+pub const MIN_FLOOR_SHARE: f32 = 0.15;
+/// Seeds tried for a level before falling back to a plain hall
+const GENERATION_ATTEMPTS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeJson, SerJson)]
+pub enum Algorithm {
+    /// Rooms connected by corridors
+    Bsp,
+    /// Rooms splitting up the whole map, sharing their walls
+    BspInterior,
+    /// Cellular automata on random noise
+    Caves,
+    /// Open halls dug out by random walks
+    Drunkard,
+    Maze,
+}
+
+impl Algorithm {
+    fn add_to(self, builder: &mut MapBuilder) {
+        match self {
+            Algorithm::Bsp => builder.with(BspRooms::new()).with(NearestCorridors::new()),
+            Algorithm::BspInterior => builder.with(BspInterior::new()),
+            Algorithm::Caves => builder
+                .with(NoiseGenerator::uniform())
+                .with(CellularAutomata::new()),
+            Algorithm::Drunkard => builder.with(DrunkardsWalk::open_halls()),
+            Algorithm::Maze => builder.with(MazeBuilder::new()),
+        };
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "bsp" => Algorithm::Bsp,
+            "bsp-interior" => Algorithm::BspInterior,
+            "caves" => Algorithm::Caves,
+            "drunkard" => Algorithm::Drunkard,
+            "maze" => Algorithm::Maze,
+            _ => bail!("unknown map algorithm {s}, use bsp, bsp-interior, caves, drunkard or maze"),
+        })
+    }
+}
+
+/// How to generate the map of a level
+#[derive(Debug, Clone, PartialEq, DeJson, SerJson)]
+pub struct MapGenConfig {
+    pub width: i32,
+    pub height: i32,
+    /// Gives the same layouts on every run, otherwise they follow the run seed
+    pub seed: Option<u64>,
+    /// Applied in order, later ones carve into what the earlier ones left
+    pub algorithms: Vec<Algorithm>,
+}
+
+impl Default for MapGenConfig {
+    fn default() -> Self {
+        Self {
+            width: 40,
+            height: 40,
+            seed: None,
+            algorithms: vec![Algorithm::Bsp],
+        }
+    }
+}
+
+impl MapGenConfig {
+    pub fn validate(&self) -> Result<()> {
+        let size = MIN_MAP_SIZE..=MAX_MAP_SIZE;
+        if !size.contains(&self.width) || !size.contains(&self.height) {
+            bail!(
+                "map size {}x{} is outside of {MIN_MAP_SIZE} to {MAX_MAP_SIZE}",
+                self.width,
+                self.height
+            );
+        }
+        if self.algorithms.is_empty() {
+            bail!("no map algorithm given");
+        }
+        Ok(())
+    }
+
+    /// The whole pipeline, every level ends up with a start and a distant exit
+    pub fn builder(&self) -> MapBuilder {
+        let mut builder = MapBuilder::new(self.width as _, self.height as _);
+        for algorithm in &self.algorithms {
+            algorithm.add_to(&mut builder);
+        }
+        builder
+            .with(AreaStartingPosition::new(XStart::LEFT, YStart::BOTTOM))
+            .with(CullUnreachable::new())
+            .with(DistantExit::new());
+        builder
+    }
+
+    /// `--algorithm caves+bsp`, `--map-size 60x40` and `--map-seed 7`,
+    /// None if none of them is given
+    pub fn from_args(args: &[String]) -> Result<Option<Self>> {
+        let algorithms: Option<String> = arg(args, "--algorithm");
+        let size: Option<String> = arg(args, "--map-size");
+        let seed: Option<u64> = arg(args, "--map-seed");
+        if algorithms.is_none() && size.is_none() && seed.is_none() {
+            return Ok(None);
+        }
+        let mut config = Self {
+            seed,
+            ..Default::default()
+        };
+        if let Some(algorithms) = algorithms {
+            config.algorithms = algorithms
+                .split('+')
+                .map(str::parse)
+                .collect::<Result<_>>()?;
+        }
+        if let Some(size) = size {
+            let (w, h) = size
+                .split_once('x')
+                .ok_or_else(|| anyhow!("--map-size wants WIDTHxHEIGHT, got {size}"))?;
+            config.width = w.parse()?;
+            config.height = h.parse()?;
+        }
+        config.validate()?;
+        Ok(Some(config))
+    }
+}
+
+/// Why a generated map can not be played
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapError {
+    NoStart,
+    NoExit,
+    /// share of the map that is floor
+    TooCramped(f32),
+    Unreachable,
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::NoStart => write!(f, "there is no starting point"),
+            MapError::NoExit => write!(f, "there is no exit apart from the start"),
+            MapError::TooCramped(share) => write!(f, "only {:.0}% of it is floor", share * 100.),
+            MapError::Unreachable => write!(f, "the exit can not be reached"),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+/// One entry of the level table
+#[derive(Debug, Clone, PartialEq, DeJson, SerJson)]
+pub struct LevelGen {
+    /// Holds from this depth on, until the entry with the next bigger one
+    pub from_depth: i32,
+    pub config: MapGenConfig,
+}
+
+/// Singleton, the map generation of every level, loaded from the data file.
+/// `forced` comes from the command line and applies to all levels.
+#[derive(Component, Debug, Clone, Default)]
+pub struct MapGenTable {
+    pub levels: Vec<LevelGen>,
+    pub forced: Option<MapGenConfig>,
+}
+
+impl MapGenTable {
+    pub fn config_for(&self, depth: i32) -> MapGenConfig {
+        if let Some(forced) = &self.forced {
+            return forced.clone();
+        }
+        self.levels
+            .iter()
+            .filter(|level| level.from_depth <= depth)
+            .max_by_key(|level| level.from_depth)
+            .map(|level| level.config.clone())
+            .unwrap_or_default()
+    }
+}
+
+pub fn load_map_gen(world: &World, json: &str, forced: Option<MapGenConfig>) -> Result<()> {
+    let levels = Vec::<LevelGen>::deserialize_json(json)
+        .map_err(|e| anyhow!("invalid level data: {e:?}"))?;
+    for level in &levels {
+        level
+            .config
+            .validate()
+            .map_err(|e| anyhow!("level data from depth {}: {e}", level.from_depth))?;
+    }
+    if let Some(forced) = &forced {
+        forced.validate()?;
+    }
+    world.set(MapGenTable { levels, forced });
+    Ok(())
+}
+
+/// Generates the map of a level with the pipeline configured for its depth.
+/// Rejected maps get another try with the next seed, after a few the level
+/// becomes a plain hall.
+pub fn level_map(world: &World, depth: i32) -> TileMap {
+    let config = world
+        .try_get::<&MapGenTable>(|table| table.config_for(depth))
+        .unwrap_or_default();
+    // a fixed seed still gives every depth it covers its own layout
+    let seed = match config.seed {
+        Some(seed) => Rng::new(seed.wrapping_add(depth as u64)).next_u64(),
+        None => world.get::<&RngStreams>(|streams| streams.level_seed(depth)),
+    };
+    for attempt in 0..GENERATION_ATTEMPTS {
+        match TileMap::new(&config, seed.wrapping_add(attempt)) {
+            Ok(tm) => return tm,
+            Err(e) => eprintln!("Rejected the map of depth {depth}: {e}"),
+        }
+    }
+    eprintln!("Giving depth {depth} a plain hall instead");
+    TileMap::hall(config.width, config.height)
+}
+
+#[derive(Component)]
+pub struct MapGenComponents {}
+
+impl Module for MapGenComponents {
+    fn module(world: &World) {
+        world.component_kf::<MapGenTable>();
+        world.set(MapGenTable::default());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn config_for_test() {
+        let json = r#"[
+            {
+                "from_depth": 1,
+                "config": {"width": 40, "height": 40, "seed": null, "algorithms": ["Bsp"]}
+            },
+            {
+                "from_depth": 4,
+                "config": {"width": 50, "height": 30, "seed": 3, "algorithms": ["Caves", "Bsp"]}
+            }
+        ]"#;
+        let world = World::new();
+        world.import::<MapGenComponents>();
+        load_map_gen(&world, json, None).unwrap();
+        let table = world.get::<&MapGenTable>(|t| t.clone());
+        assert_eq!(vec![Algorithm::Bsp], table.config_for(3).algorithms);
+        let deep = table.config_for(9);
+        assert_eq!((50, 30, Some(3)), (deep.width, deep.height, deep.seed));
+        assert_eq!(vec![Algorithm::Caves, Algorithm::Bsp], deep.algorithms);
+
+        let too_small = json.replace("\"width\": 50", "\"width\": 5");
+        assert!(load_map_gen(&world, &too_small, None).is_err());
+    }
+
+    #[test]
+    fn from_args_test() {
+        let args = |s: &str| s.split(' ').map(String::from).collect::<Vec<_>>();
+        assert_eq!(
+            None,
+            MapGenConfig::from_args(&args("game --seed 3")).unwrap()
+        );
+        let config = MapGenConfig::from_args(&args("game --algorithm caves+maze --map-size 60x30"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(vec![Algorithm::Caves, Algorithm::Maze], config.algorithms);
+        assert_eq!((60, 30), (config.width, config.height));
+        assert!(MapGenConfig::from_args(&args("game --algorithm rooms")).is_err());
+        assert!(MapGenConfig::from_args(&args("game --map-size 500x20")).is_err());
+    }
+
+    #[test]
+    fn every_algorithm_test() {
+        for algorithm in [
+            Algorithm::Bsp,
+            Algorithm::BspInterior,
+            Algorithm::Caves,
+            Algorithm::Drunkard,
+            Algorithm::Maze,
+        ] {
+            let config = MapGenConfig {
+                algorithms: vec![algorithm],
+                ..Default::default()
+            };
+            // some seeds fail validation, level_map then tries the next one
+            let valid = (0..GENERATION_ATTEMPTS).any(|seed| TileMap::new(&config, seed).is_ok());
+            assert!(valid, "{algorithm:?} never gives a valid map");
+        }
+    }
+}
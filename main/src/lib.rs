@@ -0,0 +1,21 @@
+pub mod ai;
+pub mod balance;
+pub mod bot;
+pub mod camera;
+pub mod dungeon;
+pub mod game;
+pub mod headless;
+pub mod input;
+pub mod levelgen;
+pub mod morgue;
+pub mod run;
+pub mod spawn;
+pub mod sprite;
+pub mod tilemap;
+pub mod ui;
+
+use base::game::{GameComponents, Player};
+use base::util::pos::Pos;
+use base::vendored::*;
+use sprite::*;
+use tilemap::*;
@@ -1,25 +1,24 @@
-mod camera;
-mod game;
-mod input;
-mod sprite;
-mod tilemap;
-
-use crate::game::GameSystems;
-use base::game::{GameComponents, Health, Player, Unit};
+use base::game::NewRunRequested;
+use base::morgue::{HighScores, HIGH_SCORE_PATH};
 use base::nanoserde::{DeJson, SerJson};
-use base::util::pos::Pos;
-use base::{register_components, vendored::*};
-use game::EguiEnabled;
+use base::register_components;
+use base::util::rng::RngStreams;
+use flecsirogue::ai::AiSystems;
+use flecsirogue::camera::{CameraComponents, CameraSystems};
+use flecsirogue::dungeon::{remember_current_level, restore_after_load, DungeonComponents};
+use flecsirogue::game::GameSystems;
+use flecsirogue::input::InputSystems;
+use flecsirogue::levelgen::MapGenConfig;
+use flecsirogue::morgue::MorgueSystems;
+use flecsirogue::run::{arg, handle_level_change, start_run, GameData};
+use flecsirogue::spawn::SpawnSystems;
+use flecsirogue::sprite::*;
+use flecsirogue::tilemap::*;
+use flecsirogue::ui::{EguiEnabled, UiSystems};
 use graphic::vendored::egui_macroquad;
-use input::InputSystems;
-use sprite::*;
-use tilemap::*;
-
-use camera::{CameraComponents, CameraSystems};
 
 use base::flecs_ecs::prelude::*;
 use graphic::macroquad::prelude::*;
-use graphic::macroquad::rand::ChooseRandom;
 
 fn window_conf() -> Conf {
     Conf {
@@ -31,7 +30,7 @@ fn window_conf() -> Conf {
 }
 
 // we use this again on loading saves
-async fn create_world() -> World {
+async fn create_world(seed: u64, map_gen: Option<MapGenConfig>) -> World {
     // not sure how to move the TextureStore into a module since it uses async for loading
     // resources
     let mut store = TextureStore::default();
@@ -44,22 +43,46 @@ async fn create_world() -> World {
         .await
         .unwrap();
     store
-        .load_texture("../assets/32rogues/monsters.png", "monsters")
+        .load_sheet("../assets/32rogues/monsters", "monsters")
+        .await
+        .unwrap();
+    store
+        .load_sheet("../assets/32rogues/animals", "animals")
+        .await
+        .unwrap();
+    store
+        .load_texture("../assets/32rogues/items.png", "items")
         .await
         .unwrap();
 
     let world = World::new();
 
     register_components(&world);
+    world.set(RngStreams::new(seed));
+    let data = GameData {
+        monsters: load_string("../assets/monsters.json").await.unwrap(),
+        spawns: load_string("../assets/spawns.json").await.unwrap(),
+        achievements: load_string("../assets/achievements.json").await.unwrap(),
+        levels: load_string("../assets/levels.json").await.unwrap(),
+        map_gen,
+    };
+    data.load(&world).unwrap();
+    world.set(HighScores::load(HIGH_SCORE_PATH));
     world.import::<SpriteComponents>();
     world.import::<TilemapComponents>();
     world.import::<CameraComponents>();
+    world.import::<DungeonComponents>();
 
     world.import::<SpriteSystems>();
     world.import::<GameSystems>();
     world.import::<CameraSystems>();
     world.import::<InputSystems>();
+    world.import::<AiSystems>();
+    world.import::<SpawnSystems>();
+    world.import::<MorgueSystems>();
     world.import::<TilemapSystems>();
+    world.import::<TilemapDrawSystems>();
+    world.import::<UiSystems>();
 
     world.add::<EguiEnabled>();
 
@@ -102,60 +125,43 @@ async fn create_world() -> World {
 use graphic::macroquad;
 #[macroquad::main(window_conf)]
 async fn main() {
-    let mut world = create_world().await;
-
-    let player = world
-        .entity_named("PlayerCharacter")
-        .set(Unit {
-            name: "Player".into(),
-        })
-        .set(Health {
-            max: 10,
-            current: 10,
-        })
-        .add::<Player>();
-
-    let mut free_positions = Vec::new();
-    world.query::<&TileMap>().singleton().build().each(|tm| {
-        for x in 0..tm.w {
-            for y in 0..tm.h {
-                let pos = Pos::new(x, y);
-                if tm[pos] == TileKind::Floor {
-                    free_positions.push(pos);
-                }
-            }
+    let args: Vec<String> = std::env::args().collect();
+    let seed = arg(&args, "--seed").unwrap_or_else(RngStreams::random_seed);
+    let map_gen = match MapGenConfig::from_args(&args) {
+        Ok(map_gen) => map_gen,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
         }
-    });
-
-    // TODO think about reproduceable seeds
-    free_positions.shuffle();
-
-    player.set(free_positions.pop().unwrap());
-    // place enemies
-    for _ in 0..10 {
-        world
-            .entity()
-            .set(Unit {
-                name: "Goblin".into(),
-            })
-            .set(Health { max: 3, current: 3 })
-            .set(free_positions.pop().unwrap());
-    }
+    };
+    let mut world = create_world(seed, map_gen.clone()).await;
+
+    start_run(&world);
 
     let mut backup = None;
 
     loop {
         clear_background(BLACK);
 
+        if world.has::<NewRunRequested>() {
+            world = create_world(RngStreams::random_seed(), map_gen.clone()).await;
+            start_run(&world);
+            println!("New run started!");
+        }
+        handle_level_change(&world);
+
         if is_key_pressed(KeyCode::F5) {
+            remember_current_level(&world);
             let s = base::persist::serialize_world(&world).serialize_json();
             backup = Some(s);
         }
         if is_key_pressed(KeyCode::F9) {
             if let Some(ref json) = backup {
-                let new_world = create_world().await;
+                // the seed gets replaced by the one in the save
+                let new_world = create_world(0, map_gen.clone()).await;
                 let ds = Vec::deserialize_json(json).unwrap();
                 base::persist::deserialize_world(&new_world, &ds);
+                restore_after_load(&new_world);
                 world = new_world;
                 println!("World reloaded!");
             }
@@ -0,0 +1,122 @@
+use base::flecs_ecs;
+use base::flecs_ecs::prelude::*;
+use base::game::{GameComponents, GameOver, Player};
+use base::morgue::{HighScores, Morgue, MorgueComponents, HIGH_SCORE_PATH, MORGUE_DIR};
+use base::util::flecs_extension::{KfWorldExtensions, QueryExtKf};
+use base::util::pos::Pos;
+use graphic::macroquad::prelude::*;
+use graphic::vendored::egui_macroquad::egui;
+
+use crate::ui::EguiEnabled;
+use crate::{TileMap, TilemapComponents};
+
+/// Singleton, set once the morgue file of the finished run got written
+#[derive(Component, Debug, Clone)]
+pub struct RunSummary {
+    /// None if the file could not be written
+    pub morgue_path: Option<String>,
+    /// Place in the high score table, starting at 0
+    pub rank: Option<usize>,
+}
+
+#[derive(Component)]
+/// Singleton tag
+/// The high score window is open while present
+pub struct ShowHighScores {}
+
+pub fn toggle_high_scores(world: &World) {
+    if world.has::<ShowHighScores>() {
+        world.remove::<ShowHighScores>();
+    } else {
+        world.add::<ShowHighScores>();
+    }
+}
+
+#[derive(Component)]
+pub struct MorgueSystems {}
+
+impl Module for MorgueSystems {
+    fn module(world: &World) {
+        world.import::<GameComponents>();
+        world.import::<MorgueComponents>();
+        world.import::<TilemapComponents>();
+        world.component_kf::<RunSummary>();
+        world.component_kf::<ShowHighScores>();
+
+        // once per run, right after the player died
+        world
+            .system_named::<(&GameOver, &TileMap, &mut HighScores, &Pos)>("WriteMorgue")
+            .term_singleton(0)
+            .term_singleton(1)
+            .term_singleton(2)
+            .with::<Player>()
+            .without::<RunSummary>()
+            .singleton()
+            .each_entity(|player, (game_over, tm, scores, pos)| {
+                let mut morgue = Morgue::collect(player, &game_over.cause);
+                morgue.map = tm.ascii_map(*pos);
+                let morgue_path = match morgue.write(MORGUE_DIR) {
+                    Ok(path) => Some(path.display().to_string()),
+                    Err(err) => {
+                        eprintln!("Could not write the morgue file: {err}");
+                        None
+                    }
+                };
+                let rank = scores.insert(morgue.high_score());
+                if let Err(err) = scores.save(HIGH_SCORE_PATH) {
+                    eprintln!("Could not save the high scores: {err}");
+                }
+                player.world().set(RunSummary { morgue_path, rank });
+            });
+
+        world
+            .system_named::<&HighScores>("ToggleHighScores")
+            .term_singleton(0)
+            .each_iter(|it, _, _| {
+                if is_key_pressed(KeyCode::H) {
+                    toggle_high_scores(&it.world());
+                }
+            });
+
+        world
+            .system_named::<&HighScores>("EguiHighScores")
+            .term_singleton(0)
+            .with::<ShowHighScores>()
+            .singleton()
+            .with::<EguiEnabled>()
+            .singleton()
+            .each_iter(|it, _, scores| {
+                let world = it.world();
+                let current = world.try_get::<&RunSummary>(|s| s.rank).flatten();
+                graphic::egui::Window::new("High scores")
+                    .collapsible(false)
+                    .show(egui(), |ui| {
+                        if scores.entries.is_empty() {
+                            ui.label("Nobody has died yet.");
+                        }
+                        graphic::egui::Grid::new("high_score_table")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for (i, entry) in scores.entries.iter().enumerate() {
+                                    let place = format!("{}.", i + 1);
+                                    if current == Some(i) {
+                                        ui.strong(place);
+                                    } else {
+                                        ui.label(place);
+                                    }
+                                    ui.label(&entry.name);
+                                    ui.label(entry.score.to_string());
+                                    ui.label(format!("Level {}", entry.level));
+                                    ui.label(format!("Depth {}", entry.depth));
+                                    ui.label(format!("{} turns", entry.turns));
+                                    ui.label(&entry.cause);
+                                    ui.end_row();
+                                }
+                            });
+                        if ui.button("Close").clicked() {
+                            world.remove::<ShowHighScores>();
+                        }
+                    });
+            });
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{anyhow, Result};
+use base::ability::{grant_ability, Ability, AbilityEffect, Mana, TargetShape};
+use base::combat::CombatStats;
+use base::equipment::{equip, Armor, EquipSlot, Equippable, Item, Ranged, Weapon};
+use base::faction::Faction;
+use base::flecs_ecs::prelude::*;
+use base::game::{
+    DamageKind, Health, LevelChange, LightSource, Perception, Player, Regeneration, Unit,
+};
+use base::hunger::Satiation;
+use base::message::{MessageCategory, MessageLog};
+use base::monster::load_bestiary;
+use base::progression::Experience;
+use base::spawn::load_spawn_table;
+use base::stats::load_achievements;
+use base::status::StatusKind;
+use base::stealth::Stealth;
+use base::util::rng::RngStreams;
+
+use crate::dungeon::change_level;
+use crate::levelgen::{load_map_gen, MapGenComponents, MapGenConfig};
+use crate::spawn::populate_current_level;
+use crate::{TileKind, TileMap};
+
+/// Contents of the data files in the assets directory
+pub struct GameData {
+    pub monsters: String,
+    pub spawns: String,
+    pub achievements: String,
+    pub levels: String,
+    /// Map generation for all levels, from the command line
+    pub map_gen: Option<MapGenConfig>,
+}
+
+impl GameData {
+    /// Reads the data files straight from disk, the game itself loads them through macroquad
+    pub fn read(assets: &std::path::Path) -> std::io::Result<Self> {
+        let read = |name: &str| std::fs::read_to_string(assets.join(name));
+        Ok(Self {
+            monsters: read("monsters.json")?,
+            spawns: read("spawns.json")?,
+            achievements: read("achievements.json")?,
+            levels: read("levels.json")?,
+            map_gen: None,
+        })
+    }
+
+    pub fn load(&self, world: &World) -> Result<()> {
+        load_bestiary(world, &self.monsters).map_err(|e| anyhow!("invalid monster data: {e:?}"))?;
+        load_spawn_table(world, &self.spawns).map_err(|e| anyhow!("invalid spawn data: {e:?}"))?;
+        load_achievements(world, &self.achievements)
+            .map_err(|e| anyhow!("invalid achievement data: {e:?}"))?;
+        world.import::<MapGenComponents>();
+        load_map_gen(world, &self.levels, self.map_gen.clone())?;
+        Ok(())
+    }
+}
+
+/// The value following `name` on the command line, if there is one that parses
+pub fn arg<T: std::str::FromStr>(args: &[String], name: &str) -> Option<T> {
+    let i = args.iter().position(|arg| arg == name)?;
+    let value = args.get(i + 1).and_then(|value| value.parse().ok());
+    if value.is_none() {
+        eprintln!("{name} is missing its value or it is invalid, ignoring it");
+    }
+    value
+}
+
+/// Moves the player to another level if they asked for it.
+/// Has to run outside of systems.
+pub fn handle_level_change(world: &World) {
+    if !world.has::<LevelChange>() {
+        return;
+    }
+    let delta = world.get::<&LevelChange>(|change| change.delta);
+    world.remove::<LevelChange>();
+    if change_level(world, delta) {
+        populate_current_level(world);
+    }
+}
+
+/// Spawns the player and the monsters into a freshly created world
+pub fn start_run(world: &World) {
+    let seed = world.get::<&RngStreams>(|streams| streams.seed);
+    world.get::<&mut MessageLog>(|ml| {
+        ml.add(MessageCategory::System, format!("Run seed {seed}."));
+        ml.add(MessageCategory::System, "You enter the dungeon.");
+    });
+
+    let player = world
+        .entity_named("PlayerCharacter")
+        .set(Unit {
+            name: "Player".into(),
+        })
+        .set(Health {
+            max: 10,
+            current: 10,
+        })
+        .set(CombatStats {
+            accuracy: 80,
+            evasion: 10,
+            crit_chance: 5,
+        })
+        .set(Experience::default())
+        .set(Regeneration {
+            turns_per_hp: 5,
+            progress: 0,
+        })
+        .set(Perception { range: 8 })
+        .set(Stealth { percent: 25 })
+        .set(Satiation::default())
+        // the lantern
+        .set(LightSource {
+            radius: 4,
+            r: 255,
+            g: 230,
+            b: 180,
+        })
+        .set(Mana {
+            max: 10,
+            current: 10,
+            turns_per_point: 3,
+            progress: 0,
+        })
+        .add_enum(Faction::Player)
+        .add::<Player>();
+    grant_ability(player, firebolt());
+    grant_ability(player, mend());
+    let sword = world
+        .entity()
+        .set(Item {
+            name: "Short Sword".into(),
+        })
+        .set(Equippable {
+            slot: EquipSlot::Weapon,
+        })
+        .set(Weapon {
+            kind: DamageKind::Cutting,
+            min_damage: 1,
+            max_damage: 3,
+            push: 1,
+            on_hit: vec![],
+        });
+    equip(player, sword);
+    let leather = world
+        .entity()
+        .set(Item {
+            name: "Leather Armor".into(),
+        })
+        .set(Equippable {
+            slot: EquipSlot::Armor,
+        })
+        .set(Armor { reduction: 1 });
+    equip(player, leather);
+    equip(player, short_bow(world));
+
+    let start = world.get::<&TileMap>(|tm| tm.find(TileKind::StairsUp));
+    player.set(start.expect("level without up stairs"));
+    populate_current_level(world);
+}
+
+fn firebolt() -> Ability {
+    Ability {
+        name: "Firebolt".into(),
+        shape: TargetShape::Single,
+        range: 6,
+        radius: 0,
+        cost: 3,
+        cooldown: 3,
+        effects: vec![
+            AbilityEffect::Damage {
+                kind: DamageKind::Fire,
+                amount: 2,
+            },
+            AbilityEffect::Status {
+                status: StatusKind::Burning,
+                turns: 2,
+            },
+        ],
+    }
+}
+
+fn mend() -> Ability {
+    Ability {
+        name: "Mend".into(),
+        shape: TargetShape::Caster,
+        range: 0,
+        radius: 0,
+        cost: 4,
+        cooldown: 10,
+        effects: vec![AbilityEffect::Heal { amount: 4 }],
+    }
+}
+
+fn short_bow(world: &World) -> EntityView {
+    world
+        .entity()
+        .set(Item {
+            name: "Short Bow".into(),
+        })
+        .set(Equippable {
+            slot: EquipSlot::Ranged,
+        })
+        .set(Weapon {
+            kind: DamageKind::Pierce,
+            min_damage: 1,
+            max_damage: 2,
+            push: 0,
+            on_hit: vec![],
+        })
+        .set(Ranged { range: 6, speed: 3 })
+}
@@ -0,0 +1,115 @@
+use base::flecs_ecs;
+use base::flecs_ecs::prelude::*;
+use base::game::{Depth, GameComponents, LightSource, Player, TurnPassed};
+use base::message::{MessageCategory, MessageLog};
+use base::spawn::{populate_level, spawn_random_encounter, SpawnTable, WanderingClock};
+use base::util::flecs_extension::QueryExtKf;
+use base::util::pos::Pos;
+use base::util::rng::RngStreams;
+
+use crate::{TileKind, TileMap, TilemapComponents, Visibility};
+
+const TORCHES_PER_LEVEL: usize = 12;
+const TORCH_SPACING: i32 = 6;
+
+/// Fills the freshly generated current level, keeping away from the player
+pub fn populate_current_level(world: &World) {
+    let depth = world.get::<&Depth>(|d| d.current);
+    let free = world.get::<&TileMap>(|tm| tm.free_floor());
+    let mut start = None;
+    world
+        .query::<&Pos>()
+        .with::<Player>()
+        .build()
+        .each(|pos| start = Some(*pos));
+    populate_level(world, depth, free, start.unwrap_or(Pos::new(0, 0)));
+    place_torches(world);
+}
+
+/// Torches go on floor tiles along walls, spaced out so rooms are not lit evenly
+fn place_torches(world: &World) {
+    let mut candidates = world.get::<&TileMap>(|tm| {
+        tm.free_floor()
+            .into_iter()
+            .filter(|pos| {
+                [(0, 1), (0, -1), (1, 0), (-1, 0)]
+                    .into_iter()
+                    .any(|dir| tm.terrain.get_opt(*pos + dir) == Some(&TileKind::Wall))
+            })
+            .collect::<Vec<_>>()
+    });
+    let mut rng = world.get::<&RngStreams>(|streams| streams.spawns.clone());
+    let mut placed: Vec<Pos> = Vec::new();
+    while placed.len() < TORCHES_PER_LEVEL && !candidates.is_empty() {
+        let i = rng.range(0, candidates.len() as i32 - 1) as usize;
+        let pos = candidates.swap_remove(i);
+        if placed.iter().any(|p| p.distance(pos) < TORCH_SPACING) {
+            continue;
+        }
+        placed.push(pos);
+        world.entity().set(pos).set(LightSource {
+            radius: 5,
+            r: 255,
+            g: 170,
+            b: 90,
+        });
+    }
+    world.get::<&mut RngStreams>(|streams| streams.spawns = rng);
+}
+
+#[derive(Component)]
+pub struct SpawnSystems {}
+
+impl Module for SpawnSystems {
+    fn module(world: &World) {
+        world.import::<GameComponents>();
+        world.import::<TilemapComponents>();
+
+        // now and then a new group shows up somewhere the player is not looking
+        world
+            .system_named::<(
+                &mut WanderingClock,
+                &SpawnTable,
+                &mut RngStreams,
+                &TileMap,
+                &Depth,
+                &mut MessageLog,
+                &Pos,
+            )>("WanderingMonsters")
+            .term_singleton(0)
+            .term_singleton(1)
+            .term_singleton(2)
+            .term_singleton(3)
+            .term_singleton(4)
+            .term_singleton(5)
+            .with::<Player>()
+            .with::<TurnPassed>()
+            .singleton()
+            .each_entity(|e, (clock, table, streams, tm, depth, ml, player_pos)| {
+                let rules = &table.wandering;
+                if rules.every_turns <= 0 {
+                    return;
+                }
+                clock.turns += 1;
+                if clock.turns < rules.every_turns {
+                    return;
+                }
+                clock.turns = 0;
+                let monsters = tm.units.len() as i32 - 1;
+                let rng = &mut streams.spawns;
+                if monsters >= rules.max_monsters || !rng.chance(rules.chance) {
+                    return;
+                }
+                let mut free: Vec<_> = tm
+                    .free_floor()
+                    .into_iter()
+                    .filter(|p| tm.visibility[*p] != Visibility::Seen)
+                    .collect();
+                let spawned =
+                    spawn_random_encounter(&e.world(), depth.current, rng, &mut free, *player_pos);
+                if !spawned.is_empty() {
+                    ml.add(MessageCategory::Movement, "You hear something approaching.");
+                }
+            });
+    }
+}
@@ -9,14 +9,23 @@ use graphic::egui;
 use graphic::macroquad::prelude::*;
 use graphic::vendored::egui_macroquad::egui;
 use std::collections::HashMap;
+use tween::{Linear, Tweener};
 
 use crate::camera::{CameraComponents, CameraWrapper};
 use crate::{FloorSprite, GameComponents, Player, TilemapComponents, Visible, WallSprite};
-use base::game::Unit;
+use base::combat::Projectile;
+use base::equipment::Item;
+use base::faction::{Faction, FactionComponents, HostilityMatrix};
+use base::game::{LightSource, SpriteRef, Unit};
+use base::loot::Corpse;
+use base::status::StatusKind;
+use base::stealth::Awareness;
 
 #[derive(Default, Component)]
 pub struct TextureStore {
     textures: HashMap<String, Texture2D>,
+    /// sprite name to source rect, per sheet
+    sheets: HashMap<String, HashMap<String, Rect>>,
 }
 
 impl TextureStore {
@@ -31,9 +40,56 @@ impl TextureStore {
         Ok(())
     }
 
+    /// Loads the texture together with the txt file describing its sprites
+    pub async fn load_sheet(
+        &mut self,
+        path: impl AsRef<str>,
+        name: impl Into<String>,
+    ) -> Result<()> {
+        let name = name.into();
+        let path = path.as_ref();
+        self.load_texture(format!("{path}.png"), name.clone())
+            .await?;
+        let description = load_string(&format!("{path}.txt")).await?;
+        self.sheets.insert(name, parse_sprite_sheet(&description));
+        Ok(())
+    }
+
     pub fn get(&self, name: impl AsRef<str>) -> Texture2D {
         self.textures[name.as_ref()].clone()
     }
+
+    /// Falls back to the first sprite of the monsters sheet for unknown names
+    pub fn sprite(&self, sprite: &SpriteRef) -> (Texture2D, Rect) {
+        let rect = self
+            .sheets
+            .get(&sprite.sheet)
+            .and_then(|sheet| sheet.get(&sprite.name.to_lowercase()));
+        match (self.textures.get(&sprite.sheet), rect) {
+            (Some(texture), Some(rect)) => (texture.clone(), *rect),
+            _ => (self.get("monsters"), Rect::new(0., 0., 32., 32.)),
+        }
+    }
+}
+
+/// Reads lines like "1.c. goblin", row numbers start at 1 and columns at a
+pub fn parse_sprite_sheet(description: &str) -> HashMap<String, Rect> {
+    let mut sprites = HashMap::new();
+    for line in description.lines() {
+        let Some((row, rest)) = line.trim().split_once('.') else {
+            continue;
+        };
+        let Some((col, name)) = rest.split_once('.') else {
+            continue;
+        };
+        let (Ok(row), Some(col)) = (row.parse::<u32>(), col.chars().next()) else {
+            continue;
+        };
+        let col = col as u32 - 'a' as u32;
+        let rect = Rect::new(32. * col as f32, 32. * (row - 1) as f32, 32., 32.);
+        sprites.insert(name.trim().to_lowercase(), rect);
+    }
+    sprites
 }
 
 #[derive(Component, Debug, Default)]
@@ -61,12 +117,45 @@ impl Into<Vec2f> for &DrawPos {
     }
 }
 
+/// Seconds a tweened entity takes to glide to its new tile
+pub const TWEEN_DURATION: f32 = 0.1;
+
+/// Makes the DrawPos glide to the Pos instead of snapping to it
+#[derive(Component)]
+pub struct DrawTween {
+    pub to: Pos,
+    pub tween: Tweener<Vec2f, f32, Linear>,
+}
+
+impl DrawTween {
+    pub fn at(pos: Pos) -> Self {
+        let p = tile_to_pixel(pos);
+        Self {
+            to: pos,
+            tween: Tweener::linear(p, p, 0.),
+        }
+    }
+}
+
+fn tile_to_pixel(pos: Pos) -> Vec2f {
+    Vec2f {
+        x: 32. * pos.x as f32,
+        y: 32. * pos.y as f32,
+    }
+}
+
 #[derive(Component)]
 pub struct Sprite {
     pub texture: Texture2D,
     pub params: DrawTextureParams,
 }
 
+/// Entities to outline on the map, e.g. the ones a hovered message is about
+#[derive(Component, Default)]
+pub struct HighlightedEntities {
+    pub entities: Vec<u64>,
+}
+
 #[derive(Component)]
 pub struct SpriteComponents {}
 
@@ -74,9 +163,12 @@ impl Module for SpriteComponents {
     fn module(world: &World) {
         world.component_kf::<DrawPos>().meta();
         world.component_kf::<Sprite>();
+        world.component_kf::<DrawTween>();
         world.component_kf::<TextureStore>();
         world.component_kf::<FloorSprite>();
         world.component_kf::<WallSprite>();
+        world.component_kf::<HighlightedEntities>();
+        world.set(HighlightedEntities::default());
     }
 }
 
@@ -87,6 +179,7 @@ impl Module for SpriteSystems {
     fn module(w: &World) {
         w.import::<SpriteComponents>();
         w.import::<GameComponents>();
+        w.import::<FactionComponents>();
         w.import::<TilemapComponents>();
         w.import::<CameraComponents>();
 
@@ -96,12 +189,44 @@ impl Module for SpriteSystems {
                 e.set(DrawPos::default()); // will be updated in same frame
             });
         w.system::<(&Pos, &mut DrawPos)>()
-            .with::<Unit>()
+            .without::<DrawTween>()
             .kind::<PreStore>()
             .each(move |(pos, dpos)| {
                 dpos.x = 32. * pos.x as f32;
                 dpos.y = 32. * pos.y as f32;
             });
+        w.system_named::<(&Pos, &mut DrawPos, &mut DrawTween)>("DrawTweenUpdate")
+            .kind::<PreStore>()
+            .each(move |(pos, dpos, tw)| {
+                if tw.to != *pos {
+                    let from = Vec2f {
+                        x: dpos.x,
+                        y: dpos.y,
+                    };
+                    tw.tween = Tweener::linear(from, tile_to_pixel(*pos), TWEEN_DURATION);
+                    tw.to = *pos;
+                }
+                if !tw.tween.is_finished() {
+                    let v = tw.tween.move_by(get_frame_time());
+                    dpos.x = v.x;
+                    dpos.y = v.y;
+                }
+            });
+        // things lying on the floor go below the units
+        w.system::<(&Sprite, &DrawPos)>()
+            .with::<Visible>()
+            .with::<Corpse>()
+            .kind::<OnStore>()
+            .each(move |(sprite, dp)| {
+                draw_texture_ex(&sprite.texture, dp.x, dp.y, GRAY, sprite.params.clone());
+            });
+        w.system::<(&Sprite, &DrawPos)>()
+            .with::<Visible>()
+            .with::<Item>()
+            .kind::<OnStore>()
+            .each(move |(sprite, dp)| {
+                draw_texture_ex(&sprite.texture, dp.x, dp.y, WHITE, sprite.params.clone());
+            });
         w.system::<(&Sprite, &DrawPos)>()
             .with::<Visible>()
             .with::<Unit>()
@@ -110,10 +235,47 @@ impl Module for SpriteSystems {
                 draw_texture_ex(&sprite.texture, dp.x, dp.y, WHITE, sprite.params.clone());
             });
 
-        w.system_named::<(&CameraWrapper, &DrawPos, &Unit)>("HoverUnitSystem")
+        // torches, lights on units are drawn as part of their sprite
+        w.system::<(&DrawPos, &LightSource)>()
+            .with::<Visible>()
+            .without::<Unit>()
+            .kind::<OnStore>()
+            .each(move |(dp, light)| {
+                let flame = Color::from_rgba(light.r, light.g, light.b, 255);
+                draw_rectangle(dp.x + 14., dp.y + 14., 4., 12., BROWN);
+                draw_circle(dp.x + 16., dp.y + 11., 4., flame);
+            });
+
+        w.system::<&DrawPos>()
+            .with::<Visible>()
+            .with::<Projectile>()
+            .kind::<OnStore>()
+            .each(move |dp| {
+                draw_circle(dp.x + 16., dp.y + 16., 4., YELLOW);
+            });
+
+        w.system_named::<&HighlightedEntities>("DrawHighlights")
+            .term_singleton(0)
+            .kind::<OnStore>()
+            .each_iter(|it, _, highlighted| {
+                let world = it.world();
+                for id in &highlighted.entities {
+                    let e = world.entity_from_id(*id);
+                    // unseen units stay hidden, even if they are part of a message
+                    if !e.is_alive() || !e.has::<Visible>() {
+                        continue;
+                    }
+                    if let Some((x, y)) = e.try_get::<&DrawPos>(|dp| (dp.x, dp.y)) {
+                        draw_rectangle_lines(x, y, 32., 32., 2., YELLOW);
+                    }
+                }
+            });
+
+        w.system_named::<(&CameraWrapper, &HostilityMatrix, &DrawPos, &Unit)>("HoverUnitSystem")
             .term_singleton(0)
+            .term_singleton(1)
             .with::<Visible>()
-            .each(|(camera, dp, unit)| {
+            .each_entity(|e, (camera, matrix, dp, unit)| {
                 let mp = camera.screen_to_world(Vec2f::from(mouse_position()));
                 let ordered = |a, b, c| (a <= b) && (b < c);
                 let mouse_hovered =
@@ -128,6 +290,15 @@ impl Module for SpriteSystems {
                                 .show(ui, |ui| {
                                     ui.label("Name:");
                                     ui.label(&unit.name);
+                                    let faction = Faction::of(e);
+                                    let attitude = matrix.attitude(faction, Faction::Player);
+                                    ui.label(format!("{faction} ({attitude})"));
+                                    ui.label(Awareness::of(e).to_string());
+                                    for kind in StatusKind::ALL {
+                                        if let Some(status) = kind.get(e) {
+                                            ui.label(format!("{kind} ({})", status.turns));
+                                        }
+                                    }
                                 });
                         });
                 }
@@ -159,8 +330,55 @@ impl Module for SpriteSystems {
             .without::<Player>()
             .kind::<OnLoad>()
             .each_entity(|e, store| {
+                let (texture, rect) = sprite_of(store, e);
                 e.set(Sprite {
-                    texture: store.get("monsters"),
+                    texture,
+                    params: DrawTextureParams {
+                        source: Some(rect),
+                        ..Default::default()
+                    },
+                });
+            });
+
+        w.system_named::<&TextureStore>("CreateSpritesCorpse")
+            .term_at(0)
+            .singleton()
+            .with::<Corpse>()
+            .without::<&mut Sprite>()
+            .kind::<OnLoad>()
+            .each_entity(|e, store| {
+                let (texture, rect) = sprite_of(store, e);
+                e.set(Sprite {
+                    texture,
+                    params: DrawTextureParams {
+                        source: Some(rect),
+                        // lying down
+                        rotation: std::f32::consts::FRAC_PI_2,
+                        ..Default::default()
+                    },
+                });
+            });
+
+        // projectiles start gliding from where they were fired
+        w.system_named::<&Pos>("CreateDrawTweenProjectile")
+            .with::<Projectile>()
+            .without::<DrawTween>()
+            .kind::<OnLoad>()
+            .each_entity(|e, pos| {
+                let p = tile_to_pixel(*pos);
+                e.set(DrawPos { x: p.x, y: p.y }).set(DrawTween::at(*pos));
+            });
+
+        w.system_named::<&TextureStore>("CreateSpritesItem")
+            .term_at(0)
+            .singleton()
+            .with::<Item>()
+            .with::<Pos>()
+            .without::<&mut Sprite>()
+            .kind::<OnLoad>()
+            .each_entity(|e, store| {
+                e.set(Sprite {
+                    texture: store.get("items"),
                     params: DrawTextureParams {
                         source: Some(Rect::new(0., 0., 32., 32.)),
                         ..Default::default()
@@ -170,5 +388,29 @@ impl Module for SpriteSystems {
     }
 }
 
+/// Units and their corpses without a SpriteRef use the fallback sprite
+fn sprite_of(store: &TextureStore, e: EntityView) -> (Texture2D, Rect) {
+    let sprite = e
+        .try_get::<&SpriteRef>(|s| s.clone())
+        .unwrap_or_else(|| SpriteRef {
+            sheet: "monsters".into(),
+            name: String::new(),
+        });
+    store.sprite(&sprite)
+}
+
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_sprite_sheet_test() {
+        let sprites =
+            parse_sprite_sheet("1.a. orc\n1.c. goblin\n\n7.b. beaver \n16.e. Sheep (ewe)\n");
+        assert_eq!(4, sprites.len());
+        assert_eq!(Rect::new(0., 0., 32., 32.), sprites["orc"]);
+        assert_eq!(Rect::new(64., 0., 32., 32.), sprites["goblin"]);
+        assert_eq!(Rect::new(32., 192., 32., 32.), sprites["beaver"]);
+        assert_eq!(Rect::new(128., 480., 32., 32.), sprites["sheep (ewe)"]);
+    }
+}
@@ -1,13 +1,20 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     ops::{Index, IndexMut},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
-use crate::{grids::Grid, Sprite};
-use ::rand::{rngs::StdRng, SeedableRng};
+use crate::levelgen::{level_map, MapError, MapGenComponents, MapGenConfig, MIN_FLOOR_SHARE};
+use crate::{grids::Grid, Sprite, SpriteComponents};
+use ::rand::{rngs::StdRng, Rng as _, SeedableRng};
 use base::flecs_ecs;
 use base::flecs_ecs::prelude::*;
-use base::{game::Player, util::flecs_extension::KfWorldExtensions};
+use base::nanoserde::{self, DeJson, SerJson};
+use base::{
+    game::{CanSee, GameComponents, LightSource, Perception, Player, Unit},
+    stats::{Statistics, StatsComponents},
+    util::flecs_extension::{KfWorldExtensions, QueryExtKf},
+};
 use graphic::macroquad::prelude::*;
 use mapgen::*;
 
@@ -20,15 +27,169 @@ pub struct TileMap {
     pub terrain: Grid<TileKind>,
     pub visibility: Grid<Visibility>,
     pub units: HashMap<Pos, Entity>,
+    /// Changes with the terrain, fields of view computed on another revision are stale
+    pub revision: u64,
+    /// Sum of all light sources reaching a tile, rebuilt every frame
+    pub light: Grid<Color>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Light everywhere, even far away from any light source
+pub const AMBIENT_LIGHT: Color = Color::new(0.04, 0.04, 0.06, 1.);
+/// Tiles darker than this can only be seen from right next to them
+pub const MIN_VISIBLE_LIGHT: f32 = 0.15;
+
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+/// Unique across all maps, so a FOV from a stored level never looks fresh
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeJson, SerJson)]
 pub enum TileKind {
     Floor,
     Wall,
+    StairsDown,
+    StairsUp,
+    DoorOpen,
+    /// Opens when bumped into
+    DoorClosed,
+    DoorLocked,
+    Water,
+    Lava,
+    Chasm,
+    Rubble,
+    Grass,
 }
 
+/// What happens to a unit standing on a tile at the end of a turn
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnEnter {
+    /// Fire damage every turn
+    Burn(i32),
+    /// Drops the unit to the level below, monsters are gone for good
+    Fall,
+}
+
+/// Everything the game needs to know about a kind of terrain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileProps {
+    pub name: &'static str,
+    /// How the tile looks in text dumps of the map
+    pub symbol: char,
+    pub walkable: bool,
+    pub blocks_vision: bool,
+    pub blocks_projectiles: bool,
+    /// Turns it takes to step onto the tile
+    pub move_cost: i32,
+    pub on_enter: Option<OnEnter>,
+    /// Drawn over the floor sprite
+    pub tint: Option<Color>,
+}
+
+impl TileKind {
+    /// The property table of all terrain
+    pub const fn props(self) -> TileProps {
+        let open = TileProps {
+            name: "floor",
+            symbol: '.',
+            walkable: true,
+            blocks_vision: false,
+            blocks_projectiles: false,
+            move_cost: 1,
+            on_enter: None,
+            tint: None,
+        };
+        let solid = TileProps {
+            walkable: false,
+            blocks_vision: true,
+            blocks_projectiles: true,
+            ..open
+        };
+        match self {
+            TileKind::Floor => open,
+            TileKind::Wall => TileProps {
+                name: "wall",
+                symbol: '#',
+                ..solid
+            },
+            TileKind::StairsDown => TileProps {
+                name: "stairs down",
+                symbol: '>',
+                ..open
+            },
+            TileKind::StairsUp => TileProps {
+                name: "stairs up",
+                symbol: '<',
+                ..open
+            },
+            TileKind::DoorOpen => TileProps {
+                name: "open door",
+                symbol: '\'',
+                ..open
+            },
+            TileKind::DoorClosed => TileProps {
+                name: "closed door",
+                symbol: '+',
+                ..solid
+            },
+            TileKind::DoorLocked => TileProps {
+                name: "locked door",
+                symbol: '=',
+                ..solid
+            },
+            TileKind::Water => TileProps {
+                name: "water",
+                symbol: '~',
+                move_cost: 2,
+                tint: Some(Color::new(0.1, 0.3, 0.9, 0.5)),
+                ..open
+            },
+            TileKind::Lava => TileProps {
+                name: "lava",
+                symbol: '^',
+                on_enter: Some(OnEnter::Burn(4)),
+                tint: Some(Color::new(1., 0.35, 0., 0.7)),
+                ..open
+            },
+            TileKind::Chasm => TileProps {
+                name: "chasm",
+                symbol: ':',
+                on_enter: Some(OnEnter::Fall),
+                tint: Some(Color::new(0., 0., 0., 0.85)),
+                ..open
+            },
+            TileKind::Rubble => TileProps {
+                name: "rubble",
+                symbol: ';',
+                move_cost: 2,
+                tint: Some(Color::new(0.45, 0.4, 0.3, 0.5)),
+                ..open
+            },
+            TileKind::Grass => TileProps {
+                name: "grass",
+                symbol: '"',
+                tint: Some(Color::new(0.2, 0.7, 0.2, 0.35)),
+                ..open
+            },
+        }
+    }
+
+    pub fn is_walkable(self) -> bool {
+        self.props().walkable
+    }
+
+    pub fn symbol(self) -> char {
+        self.props().symbol
+    }
+
+    /// Walkable, but hurts or worse
+    pub fn is_hazard(self) -> bool {
+        self.props().on_enter.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, DeJson, SerJson)]
 pub enum Visibility {
     Unseen,
     Seen,
@@ -36,15 +197,12 @@ pub enum Visibility {
 }
 
 impl TileMap {
-    pub fn new() -> Self {
-        let mut rng = StdRng::seed_from_u64(2234);
-        let (w, h): (i32, i32) = (40, 40);
-        let map = MapBuilder::new(w as _, h as _)
-            .with(BspRooms::new())
-            .with(NearestCorridors::new())
-            .with(AreaStartingPosition::new(XStart::LEFT, YStart::BOTTOM))
-            .with(DistantExit::new())
-            .build_with_rng(&mut rng);
+    /// Generates the layout of a level, the same config and seed give the same layout.
+    /// Fails if the map is not fit to play on.
+    pub fn new(config: &MapGenConfig, seed: u64) -> Result<Self, MapError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (w, h) = (config.width, config.height);
+        let map = config.builder().build_with_rng(&mut rng);
         let mut terrain = Grid::new(w, h, TileKind::Wall);
         for x in 0..w {
             for y in 0..h {
@@ -53,14 +211,304 @@ impl TileMap {
                 }
             }
         }
-        let visibility = Grid::new(w, h, Visibility::Unseen);
+        let start = map
+            .starting_point
+            .map(|p| Pos::new(p.x as i32, p.y as i32))
+            .ok_or(MapError::NoStart)?;
+        let exit = map
+            .exit_point
+            .map(|p| Pos::new(p.x as i32, p.y as i32))
+            .filter(|exit| *exit != start)
+            .ok_or(MapError::NoExit)?;
+        // the player arrives on the up stairs, on the first level too
+        terrain[start] = TileKind::StairsUp;
+        terrain[exit] = TileKind::StairsDown;
+        decorate(&mut terrain, &mut rng);
+        let tm = Self::from_parts(terrain, Grid::new(w, h, Visibility::Unseen));
+        let floor = tm.terrain.iter_values().filter(|t| t.is_walkable()).count();
+        let share = floor as f32 / (w * h) as f32;
+        if share < MIN_FLOOR_SHARE {
+            return Err(MapError::TooCramped(share));
+        }
+        if !tm.connects(start, exit) {
+            return Err(MapError::Unreachable);
+        }
+        Ok(tm)
+    }
+
+    /// One room filling the whole map with the stairs in opposite corners.
+    /// Always playable, for when the generated maps keep getting rejected.
+    pub fn hall(w: i32, h: i32) -> Self {
+        let mut terrain = Grid::filled_with(w, h, |x, y| {
+            if x == 0 || y == 0 || x == w - 1 || y == h - 1 {
+                TileKind::Wall
+            } else {
+                TileKind::Floor
+            }
+        });
+        terrain[(1, h - 2)] = TileKind::StairsUp;
+        terrain[(w - 2, 1)] = TileKind::StairsDown;
+        Self::from_parts(terrain, Grid::new(w, h, Visibility::Unseen))
+    }
 
+    /// Whether `to` can be reached from `from` without stepping into hazards,
+    /// closed doors are no obstacle
+    fn connects(&self, from: Pos, to: Pos) -> bool {
+        let passable = |pos: Pos| {
+            self.terrain.get_opt(pos).is_some_and(|tile| {
+                (tile.is_walkable() || *tile == TileKind::DoorClosed) && !tile.is_hazard()
+            })
+        };
+        let mut seen = HashSet::from([from]);
+        let mut frontier = VecDeque::from([from]);
+        while let Some(pos) = frontier.pop_front() {
+            if pos == to {
+                return true;
+            }
+            for next in pos.neighbors() {
+                if passable(next) && seen.insert(next) {
+                    frontier.push_back(next);
+                }
+            }
+        }
+        false
+    }
+
+    /// A level from its terrain and what the player knows of it,
+    /// with nobody on it and no light computed yet
+    pub fn from_parts(terrain: Grid<TileKind>, visibility: Grid<Visibility>) -> Self {
+        let (w, h) = (terrain.width, terrain.height);
         Self {
             w,
             h,
             terrain,
             visibility,
             units: Default::default(),
+            revision: next_revision(),
+            light: Grid::new(w, h, AMBIENT_LIGHT),
+        }
+    }
+
+    /// Brightest channel of the light on the tile
+    pub fn light_level(&self, pos: Pos) -> f32 {
+        self.light
+            .get_opt(pos)
+            .map_or(0., |c| c.r.max(c.g).max(c.b))
+    }
+
+    /// Whether `observer` can make out what is on `pos`, given it is in its field of view
+    pub fn is_lit_for(&self, observer: Pos, pos: Pos) -> bool {
+        observer.distance(pos) <= 1 || self.light_level(pos) >= MIN_VISIBLE_LIGHT
+    }
+
+    /// Adds the light of one source, fading towards the edge of its radius
+    pub fn add_light(&mut self, origin: Pos, light: &LightSource) {
+        let color = Color::from_rgba(light.r, light.g, light.b, 255);
+        for pos in self.compute_fov(origin, light.radius) {
+            let falloff = 1. - origin.distance(pos) as f32 / (light.radius + 1) as f32;
+            let tile = &mut self.light[pos];
+            tile.r = (tile.r + color.r * falloff).min(1.);
+            tile.g = (tile.g + color.g * falloff).min(1.);
+            tile.b = (tile.b + color.b * falloff).min(1.);
+        }
+    }
+
+    /// Steps a noise needs to reach each tile, walls swallow it
+    pub fn noise_reach(&self, origin: Pos, loudness: i32) -> HashMap<Pos, i32> {
+        let mut reached = HashMap::from([(origin, 0)]);
+        let mut frontier = VecDeque::from([origin]);
+        while let Some(pos) = frontier.pop_front() {
+            let distance = reached[&pos];
+            if distance >= loudness {
+                continue;
+            }
+            for next in pos.neighbors() {
+                if self.is_walkable(next) && !reached.contains_key(&next) {
+                    reached.insert(next, distance + 1);
+                    frontier.push_back(next);
+                }
+            }
+        }
+        reached
+    }
+
+    /// Changes a tile and lets every field of view know about it
+    pub fn set_tile(&mut self, pos: Pos, kind: TileKind) {
+        self.terrain[pos] = kind;
+        self.revision = next_revision();
+    }
+
+    /// Symmetric shadowcasting from `origin`, tiles further away than `range` stay dark
+    pub fn compute_fov(&self, origin: Pos, range: i32) -> HashSet<Pos> {
+        let mut tiles = HashSet::new();
+        let mut blocks_vision = |pos| {
+            let pos = Pos::from(pos);
+            origin.distance(pos) > range
+                || self
+                    .terrain
+                    .get_opt(pos)
+                    .map_or(true, |t| t.props().blocks_vision)
+        };
+        let mut mark_visible = |pos| {
+            let pos = Pos::from(pos);
+            if origin.distance(pos) <= range && self.terrain.get_opt(pos).is_some() {
+                tiles.insert(pos);
+            }
+        };
+        symmetric_shadowcasting::compute_fov(origin.into(), &mut blocks_vision, &mut mark_visible);
+        tiles
+    }
+
+    /// Floor tiles nobody stands on
+    pub fn free_floor(&self) -> Vec<Pos> {
+        let mut free = Vec::new();
+        for x in 0..self.w {
+            for y in 0..self.h {
+                let pos = Pos::new(x, y);
+                if self[pos] == TileKind::Floor && !self.units.contains_key(&pos) {
+                    free.push(pos);
+                }
+            }
+        }
+        free
+    }
+
+    /// The explored part of the map as text, `@` marks the player
+    pub fn ascii_map(&self, player: Pos) -> Vec<String> {
+        (0..self.h)
+            .map(|y| {
+                let row: String = (0..self.w)
+                    .map(|x| {
+                        let pos = Pos::new(x, y);
+                        if pos == player {
+                            '@'
+                        } else if self.visibility[pos] == Visibility::Unseen {
+                            ' '
+                        } else {
+                            self[pos].symbol()
+                        }
+                    })
+                    .collect();
+                row.trim_end().to_owned()
+            })
+            .collect()
+    }
+
+    /// First tile of the given kind
+    pub fn find(&self, kind: TileKind) -> Option<Pos> {
+        self.terrain
+            .iter_coords()
+            .find(|(_, tile)| **tile == kind)
+            .map(|(pos, _)| pos)
+    }
+
+    pub fn is_walkable(&self, pos: Pos) -> bool {
+        self.terrain
+            .get_opt(pos)
+            .is_some_and(|tile| tile.is_walkable())
+    }
+
+    /// Outside of the map counts as blocking
+    pub fn blocks_projectiles(&self, pos: Pos) -> bool {
+        self.terrain
+            .get_opt(pos)
+            .map_or(true, |tile| tile.props().blocks_projectiles)
+    }
+
+    /// Walls and closed doors block the shot, so does any unit in front of the target
+    pub fn line_of_fire(&self, from: Pos, to: Pos) -> bool {
+        let path = from.line_to(to);
+        path.iter().enumerate().all(|(i, pos)| {
+            let is_target = i + 1 == path.len();
+            !self.blocks_projectiles(*pos) && (is_target || !self.units.contains_key(pos))
+        })
+    }
+}
+
+/// Chance for a gap between a corridor and a room to get a door
+const DOOR_CHANCE: f64 = 0.4;
+
+/// Kind, number of patches and tiles per patch
+const PATCHES: [(TileKind, usize, usize); 5] = [
+    (TileKind::Grass, 3, 12),
+    (TileKind::Water, 2, 10),
+    (TileKind::Rubble, 2, 5),
+    (TileKind::Lava, 1, 5),
+    (TileKind::Chasm, 1, 3),
+];
+
+/// Puts doors and patches of other terrain onto the plain floor of a level.
+/// Hazards only go where they can be walked around.
+fn decorate(terrain: &mut Grid<TileKind>, rng: &mut StdRng) {
+    let walkable = |terrain: &Grid<TileKind>, pos: Pos| {
+        terrain.get_opt(pos).is_some_and(|tile| tile.is_walkable())
+    };
+    let roomy = |terrain: &Grid<TileKind>, pos: Pos| {
+        pos.neighbors()
+            .into_iter()
+            .filter(|n| walkable(terrain, *n))
+            .count()
+            >= 5
+    };
+    for pos in terrain.coords() {
+        if terrain[pos] != TileKind::Floor {
+            continue;
+        }
+        let wall = |dir: (i32, i32)| terrain.get_opt(pos + dir) == Some(&TileKind::Wall);
+        let gap = [((1, 0), (0, 1)), ((0, 1), (1, 0))]
+            .into_iter()
+            .find(|(across, _)| wall(*across) && wall((-across.0, -across.1)));
+        let Some((_, along)) = gap else {
+            continue;
+        };
+        let ends = [pos + along, pos - along];
+        let into_room = ends.iter().filter(|end| roomy(terrain, **end)).count() == 1;
+        let door_near = pos.neighbors().iter().any(|n| {
+            matches!(
+                terrain.get_opt(*n),
+                Some(TileKind::DoorClosed | TileKind::DoorOpen)
+            )
+        });
+        let fits = into_room && !door_near && ends.iter().all(|end| walkable(terrain, *end));
+        if fits && rng.gen_bool(DOOR_CHANCE) {
+            terrain[pos] = if rng.gen_bool(0.3) {
+                TileKind::DoorOpen
+            } else {
+                TileKind::DoorClosed
+            };
+        }
+    }
+
+    let open_floor = |terrain: &Grid<TileKind>, pos: Pos| {
+        terrain.get_opt(pos) == Some(&TileKind::Floor)
+            && pos.neighbors().iter().all(|n| walkable(terrain, *n))
+    };
+    for (kind, patches, size) in PATCHES {
+        for _ in 0..patches {
+            let candidates: Vec<Pos> = terrain
+                .coords()
+                .into_iter()
+                .filter(|pos| open_floor(terrain, *pos))
+                .collect();
+            if candidates.is_empty() {
+                return;
+            }
+            let mut pos = candidates[rng.gen_range(0..candidates.len())];
+            for _ in 0..size {
+                let fits = if kind.is_hazard() {
+                    open_floor(terrain, pos)
+                } else {
+                    terrain.get_opt(pos) == Some(&TileKind::Floor)
+                };
+                if fits {
+                    terrain[pos] = kind;
+                }
+                let neighbors = pos.neighbors();
+                let next = neighbors[rng.gen_range(0..neighbors.len())];
+                if walkable(terrain, next) {
+                    pos = next;
+                }
+            }
         }
     }
 }
@@ -96,12 +544,23 @@ pub struct FloorSprite {
 #[derive(Component)]
 pub struct Visible {}
 
+/// The tiles a unit sees, recomputed when it moves or the terrain changes
+#[derive(Component, Default)]
+pub struct Fov {
+    pub origin: Option<Pos>,
+    pub revision: u64,
+    pub tiles: HashSet<Pos>,
+}
+
 #[derive(Component)]
 pub struct TilemapComponents {}
 
 impl Module for TilemapComponents {
     fn module(world: &flecs_ecs::prelude::World) {
+        world.import::<MapGenComponents>();
+
         world.component_kf::<Visible>();
+        world.component_kf::<Fov>();
         world.component_kf::<TileMap>();
     }
 }
@@ -111,9 +570,11 @@ pub struct TilemapSystems {}
 
 impl Module for TilemapSystems {
     fn module(world: &flecs_ecs::prelude::World) {
+        world.import::<GameComponents>();
         world.import::<TilemapComponents>();
+        world.import::<StatsComponents>();
         // TODO move to init function
-        world.set(TileMap::new());
+        world.set(level_map(world, 1));
 
         world
             .system_named::<&mut TileMap>("TileMap:UnitClearPos")
@@ -127,10 +588,78 @@ impl Module for TilemapSystems {
             .system_named::<(&mut TileMap, &Pos)>("TileMap:UnitUpdatePos")
             .term_at(0)
             .singleton()
+            .with::<Unit>()
             .each_entity(|e, (tm, pos)| {
                 tm.units.insert(*pos, *e);
             });
 
+        world
+            .system_named::<&Pos>("TileMap:FOVAdd")
+            .with::<Unit>()
+            .without::<Fov>()
+            .each_entity(|e, _| {
+                e.set(Fov::default());
+            });
+
+        world
+            .system_named::<(&TileMap, &Pos, &mut Fov)>("TileMap:FOVCompute")
+            .term_singleton(0)
+            .with::<Unit>()
+            .each_entity(|e, (tm, pos, fov)| {
+                if fov.origin == Some(*pos) && fov.revision == tm.revision {
+                    return;
+                }
+                fov.tiles = tm.compute_fov(*pos, Perception::of(e));
+                fov.origin = Some(*pos);
+                fov.revision = tm.revision;
+            });
+
+        world
+            .system_named::<&mut TileMap>("TileMap:LightClear")
+            .term_singleton(0)
+            .each(|tm| {
+                for light in tm.light.iter_values_mut() {
+                    *light = AMBIENT_LIGHT;
+                }
+            });
+
+        world
+            .system_named::<(&mut TileMap, &LightSource, &Pos)>("TileMap:LightRefresh")
+            .term_singleton(0)
+            .each(|(tm, light, pos)| {
+                tm.add_light(*pos, light);
+            });
+
+        // only lit units get noticed, unless they are right next to the observer
+        world
+            .system_named::<(&TileMap, &Fov, &Pos)>("TileMap:CanSee")
+            .term_singleton(0)
+            .with::<Unit>()
+            .each_entity(|e, (tm, fov, observer)| {
+                let mut seen: Vec<Entity> = tm
+                    .units
+                    .iter()
+                    .filter(|(pos, other)| **other != *e && fov.tiles.contains(pos))
+                    .filter(|(pos, _)| tm.is_lit_for(*observer, **pos))
+                    .map(|(_, other)| *other)
+                    .collect();
+                let mut lost = Vec::new();
+                e.each_target::<CanSee>(|target| {
+                    let target = target.id();
+                    if seen.contains(&target) {
+                        seen.retain(|s| *s != target);
+                    } else {
+                        lost.push(target);
+                    }
+                });
+                for target in lost {
+                    e.remove_first::<CanSee>(target);
+                }
+                for target in seen {
+                    e.add_first::<CanSee>(target);
+                }
+            });
+
         world
             .system_named::<&mut TileMap>("TileMap:FOVClear")
             .term_at(0)
@@ -144,21 +673,21 @@ impl Module for TilemapSystems {
                 }
             });
         world
-            .system_named::<(&mut TileMap, &Pos)>("TileMap:FOVRefresh")
+            .system_named::<(&mut TileMap, &mut Statistics, &Fov, &Pos)>("TileMap:FOVRefresh")
             .term_at(0)
             .singleton()
+            .term_at(1)
+            .singleton()
             .with::<Player>()
-            .each(|(tm, player_pos)| {
-                let terrain = &tm.terrain;
-                let visibility = &mut tm.visibility;
-                // TODO add max vision length by blocking everything from some range?
-                let mut blocks_vision = |pos| terrain[Pos::from(pos)] == TileKind::Wall;
-                let mut mark_visible = |pos| visibility[Pos::from(pos)] = Visibility::Seen;
-                symmetric_shadowcasting::compute_fov(
-                    player_pos.into(),
-                    &mut blocks_vision,
-                    &mut mark_visible,
-                );
+            .each(|(tm, stats, fov, player_pos)| {
+                for pos in &fov.tiles {
+                    if tm.is_lit_for(*player_pos, *pos) {
+                        if tm.visibility[*pos] == Visibility::Unseen {
+                            stats.tiles_explored += 1;
+                        }
+                        tm.visibility[*pos] = Visibility::Seen;
+                    }
+                }
             });
 
         world
@@ -173,6 +702,17 @@ impl Module for TilemapSystems {
                     _ => e.remove::<Visible>(),
                 };
             });
+    }
+}
+
+/// Draws the map, only for the windowed game
+#[derive(Component)]
+pub struct TilemapDrawSystems {}
+
+impl Module for TilemapDrawSystems {
+    fn module(world: &flecs_ecs::prelude::World) {
+        world.import::<TilemapComponents>();
+        world.import::<SpriteComponents>();
 
         world
             .system_named::<(&TileMap, &WallSprite, &FloorSprite)>("TileMap:DrawTilemap")
@@ -187,14 +727,10 @@ impl Module for TilemapSystems {
                     let (fx, fy) = (pos.x as f32 * 32., pos.y as f32 * 32.);
                     let color = match tm.visibility[pos] {
                         Visibility::Unseen => BLACK,
-                        Visibility::Seen => WHITE,
+                        Visibility::Seen => tile_shade(tm, pos),
                         Visibility::Remembered => DARKGRAY,
                     };
                     match tm.terrain[pos] {
-                        TileKind::Floor => {
-                            let s = floor_s;
-                            draw_texture_ex(&s.texture, fx, fy, color, s.params.clone());
-                        }
                         TileKind::Wall => {
                             let below = pos + (0, 1);
                             let s = match tm.terrain.get_opt(below) {
@@ -203,8 +739,189 @@ impl Module for TilemapSystems {
                             };
                             draw_texture_ex(&s.texture, fx, fy, color, s.params.clone());
                         }
+                        kind => {
+                            let s = floor_s;
+                            draw_texture_ex(&s.texture, fx, fy, color, s.params.clone());
+                            let props = kind.props();
+                            if let Some(tint) = props.tint {
+                                let shaded = Color::new(
+                                    tint.r * color.r,
+                                    tint.g * color.g,
+                                    tint.b * color.b,
+                                    tint.a,
+                                );
+                                draw_rectangle(fx, fy, 32., 32., shaded);
+                            }
+                            if kind != TileKind::Floor {
+                                let glyph = props.symbol.to_string();
+                                draw_text(&glyph, fx + 9., fy + 24., 32., color);
+                            }
+                        }
                     };
                 }
             });
     }
 }
+
+/// Tint of a seen tile, dark tiles next to the player stay faintly visible
+fn tile_shade(tm: &TileMap, pos: Pos) -> Color {
+    let light = tm.light[pos];
+    let min = 0.3;
+    Color::new(light.r.max(min), light.g.max(min), light.b.max(min), 1.)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ascii_map_test() {
+        let mut tm = TileMap {
+            w: 5,
+            h: 3,
+            terrain: Grid::new(5, 3, TileKind::Wall),
+            visibility: Grid::new(5, 3, Visibility::Remembered),
+            units: Default::default(),
+            revision: 0,
+            light: Grid::new(5, 3, AMBIENT_LIGHT),
+        };
+        tm[Pos::new(1, 1)] = TileKind::Floor;
+        tm[Pos::new(2, 1)] = TileKind::StairsDown;
+        tm[Pos::new(3, 1)] = TileKind::Floor;
+        tm.visibility[Pos::new(4, 1)] = Visibility::Unseen;
+        assert_eq!(vec!["#####", "#@>.", "#####"], tm.ascii_map(Pos::new(1, 1)));
+    }
+
+    #[test]
+    fn can_see_test() {
+        let world = World::new();
+        world.import::<GameComponents>();
+        world.import::<TilemapSystems>();
+
+        let mut tm = TileMap {
+            w: 10,
+            h: 5,
+            terrain: Grid::new(10, 5, TileKind::Floor),
+            visibility: Grid::new(10, 5, Visibility::Unseen),
+            units: Default::default(),
+            revision: 0,
+            light: Grid::new(10, 5, AMBIENT_LIGHT),
+        };
+        tm.set_tile(Pos::new(1, 2), TileKind::Wall);
+        world.set(tm);
+
+        let unit = |name: &str, pos| world.entity().set(Unit { name: name.into() }).set(pos);
+        let observer = unit("Observer", Pos::new(1, 1)).set(Perception { range: 3 });
+        let near = unit("Near", Pos::new(4, 1));
+        let far = unit("Far", Pos::new(5, 1));
+        let hidden = unit("Hidden", Pos::new(1, 3));
+
+        world.progress();
+        world.progress();
+        // in the dark only neighbours get noticed
+        assert!(!observer.has_first::<CanSee>(near));
+
+        observer.set(LightSource {
+            radius: 10,
+            r: 255,
+            g: 200,
+            b: 150,
+        });
+        world.progress();
+        assert!(observer.has_first::<CanSee>(near));
+        assert!(!observer.has_first::<CanSee>(far));
+        assert!(!observer.has_first::<CanSee>(hidden));
+        // without a Perception the default range applies
+        assert!(far.has_first::<CanSee>(observer));
+
+        // moving recomputes the field of view
+        near.set(Pos::new(8, 4));
+        observer.set(Pos::new(2, 3));
+        world.progress();
+        world.progress();
+        assert!(!observer.has_first::<CanSee>(near));
+        assert!(observer.has_first::<CanSee>(hidden));
+        assert!(observer.has_first::<CanSee>(far));
+
+        // so does a change in terrain
+        world.get::<&mut TileMap>(|tm| {
+            for y in 0..5 {
+                tm.set_tile(Pos::new(3, y), TileKind::Wall);
+            }
+        });
+        world.progress();
+        world.progress();
+        assert!(observer.has_first::<CanSee>(hidden));
+        assert!(!observer.has_first::<CanSee>(far));
+    }
+
+    #[test]
+    fn terrain_props_test() {
+        let mut tm = TileMap {
+            w: 7,
+            h: 1,
+            terrain: Grid::new(7, 1, TileKind::Floor),
+            visibility: Grid::new(7, 1, Visibility::Remembered),
+            units: Default::default(),
+            revision: 0,
+            light: Grid::new(7, 1, AMBIENT_LIGHT),
+        };
+        let (from, to, middle) = (Pos::new(0, 0), Pos::new(6, 0), Pos::new(3, 0));
+        let sees_through = |tm: &TileMap| tm.compute_fov(from, 8).contains(&to);
+
+        tm.set_tile(middle, TileKind::Water);
+        assert!(tm.is_walkable(middle));
+        assert_eq!(2, tm[middle].props().move_cost);
+        assert!(tm.line_of_fire(from, to));
+        assert!(sees_through(&tm));
+
+        tm.set_tile(middle, TileKind::DoorClosed);
+        assert!(!tm.is_walkable(middle));
+        assert!(!tm.line_of_fire(from, to));
+        assert!(!sees_through(&tm));
+
+        tm.set_tile(middle, TileKind::DoorOpen);
+        assert!(tm.line_of_fire(from, to));
+        assert!(sees_through(&tm));
+
+        let kinds = [
+            TileKind::Floor,
+            TileKind::Wall,
+            TileKind::StairsDown,
+            TileKind::StairsUp,
+            TileKind::DoorOpen,
+            TileKind::DoorClosed,
+            TileKind::DoorLocked,
+            TileKind::Water,
+            TileKind::Lava,
+            TileKind::Chasm,
+            TileKind::Rubble,
+            TileKind::Grass,
+        ];
+        let symbols: HashSet<char> = kinds.iter().map(|k| k.symbol()).collect();
+        assert_eq!(kinds.len(), symbols.len());
+    }
+
+    #[test]
+    fn decorate_test() {
+        for seed in 1..6 {
+            let tm = TileMap::new(&MapGenConfig::default(), seed).unwrap();
+            assert!(tm.find(TileKind::StairsUp).is_some());
+            assert!(tm.find(TileKind::StairsDown).is_some());
+            // hazards never block the way
+            for (pos, tile) in tm.terrain.iter_coords() {
+                if tile.is_hazard() {
+                    assert!(pos.neighbors().iter().all(|n| tm.is_walkable(*n)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hall_test() {
+        let tm = TileMap::hall(20, 20);
+        let up = tm.find(TileKind::StairsUp).unwrap();
+        let down = tm.find(TileKind::StairsDown).unwrap();
+        assert!(tm.connects(up, down));
+    }
+}
@@ -0,0 +1,248 @@
+//! The egui windows around the map. Only the windowed game imports these,
+//! the headless simulation runs without them.
+
+use std::collections::HashSet;
+
+use base::ability::{abilities, ability_ready, Ability, Cooldown, Mana};
+use base::combat::CombatStats;
+use base::equipment::{armor_value, EquipSlot, Item};
+use base::flecs_ecs;
+use base::flecs_ecs::prelude::*;
+use base::game::{Depth, GameOver, Health, NewRunRequested, Player, Unit};
+use base::hunger::{HungerState, Satiation};
+use base::message::{MessageCategory, MessageLog, Severity};
+use base::morgue::Kills;
+use base::progression::Experience;
+use base::stats::{AchievementList, Statistics};
+use base::util::flecs_extension::KfWorldExtensions;
+use base::util::pos::Pos;
+use graphic::vendored::egui_macroquad::egui;
+
+use crate::game::{activate_ability, GameSystems};
+use crate::morgue::{toggle_high_scores, RunSummary};
+use crate::{HighlightedEntities, SpriteComponents};
+
+#[derive(Component)]
+pub struct EguiEnabled {}
+
+/// Categories hidden in the message log window
+#[derive(Component, Default)]
+pub struct MessageLogFilter {
+    pub hidden: HashSet<MessageCategory>,
+}
+
+#[derive(Component)]
+pub struct UiSystems {}
+
+impl Module for UiSystems {
+    fn module(world: &World) {
+        world.import::<GameSystems>();
+        world.import::<SpriteComponents>();
+        world.component_kf::<EguiEnabled>();
+        world.component_kf::<MessageLogFilter>();
+        world.set(MessageLogFilter::default());
+
+        world
+            .system_named::<(&MessageLog, &mut MessageLogFilter, &mut HighlightedEntities)>(
+                "EguiMessageLog",
+            )
+            .term_singleton(0)
+            .term_singleton(1)
+            .term_singleton(2)
+            .with::<EguiEnabled>()
+            .singleton()
+            .each(|(ml, filter, highlighted)| {
+                highlighted.entities.clear();
+                graphic::egui::Window::new("Message Log").show(egui(), |ui| {
+                    ui.horizontal(|ui| {
+                        for category in MessageCategory::ALL {
+                            let mut shown = !filter.hidden.contains(&category);
+                            if ui.checkbox(&mut shown, category.to_string()).changed() {
+                                if shown {
+                                    filter.hidden.remove(&category);
+                                } else {
+                                    filter.hidden.insert(category);
+                                }
+                            }
+                        }
+                    });
+                    ui.separator();
+                    graphic::egui::ScrollArea::vertical()
+                        .stick_to_bottom(true)
+                        .show(ui, |ui| {
+                            let shown = ml
+                                .messages
+                                .iter()
+                                .filter(|msg| !filter.hidden.contains(&msg.category));
+                            for msg in shown {
+                                let text = format!("[{}] {}", msg.turn, msg.text);
+                                let text = graphic::egui::RichText::new(text)
+                                    .color(severity_color(msg.severity));
+                                if ui.label(text).hovered() {
+                                    highlighted.entities.extend(&msg.entities);
+                                }
+                            }
+                        });
+                });
+            });
+
+        world
+            .system_named::<&GameOver>("EguiDeathScreen")
+            .term_singleton(0)
+            .with::<EguiEnabled>()
+            .singleton()
+            .each_iter(|it, _, game_over| {
+                graphic::egui::Window::new("You died")
+                    .anchor(graphic::egui::Align2::CENTER_CENTER, [0., 0.])
+                    .collapsible(false)
+                    .resizable(false)
+                    .show(egui(), |ui| {
+                        ui.label(&game_over.cause);
+                        let summary = it.world().try_get::<&RunSummary>(|s| s.clone());
+                        if let Some(path) = summary.as_ref().and_then(|s| s.morgue_path.as_ref()) {
+                            ui.label(format!("Morgue file written to {path}"));
+                        }
+                        if let Some(rank) = summary.and_then(|s| s.rank) {
+                            ui.label(format!("You made place {} of the high scores!", rank + 1));
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.button("Start a new run").clicked() {
+                                it.world().add::<NewRunRequested>();
+                            }
+                            if ui.button("High scores").clicked() {
+                                toggle_high_scores(&it.world());
+                            }
+                        });
+                    });
+            });
+
+        world
+            .system_named::<(&mut MessageLog, &Pos)>("EguiHotbar")
+            .term_singleton(0)
+            .with::<Player>()
+            .with::<EguiEnabled>()
+            .singleton()
+            .without::<GameOver>()
+            .singleton()
+            .each_entity(|player, (ml, pos)| {
+                let world = player.world();
+                graphic::egui::Window::new("Abilities")
+                    .anchor(graphic::egui::Align2::CENTER_BOTTOM, [0., -10.])
+                    .title_bar(false)
+                    .resizable(false)
+                    .show(egui(), |ui| {
+                        ui.horizontal(|ui| {
+                            for (i, ability) in abilities(player).into_iter().enumerate() {
+                                let ability = world.entity_from_id(ability);
+                                let (name, cost) =
+                                    ability.get::<&Ability>(|a| (a.name.clone(), a.cost));
+                                let label = match ability.try_get::<&Cooldown>(|cd| cd.turns) {
+                                    Some(turns) => format!("{} {name} ({turns})", i + 1),
+                                    None => format!("{} {name} [{cost}]", i + 1),
+                                };
+                                let ready = ability_ready(player, ability).is_ok();
+                                if ui
+                                    .add_enabled(ready, graphic::egui::Button::new(label))
+                                    .clicked()
+                                {
+                                    activate_ability(player, ability, *pos, ml);
+                                }
+                            }
+                        });
+                    });
+            });
+
+        world
+            .system_named::<(&Unit, &Health, &Experience)>("EguiCharacterSheet")
+            .with::<Player>()
+            .with::<EguiEnabled>()
+            .singleton()
+            .each_entity(|e, (unit, hp, exp)| {
+                let stats = CombatStats::of(e);
+                graphic::egui::Window::new("Character").show(egui(), |ui| {
+                    ui.heading(&unit.name);
+                    ui.label(format!("Level {}", exp.level));
+                    ui.label(format!("Depth {}", e.world().get::<&Depth>(|d| d.current)));
+                    ui.label(format!("Experience {} / {}", exp.xp, exp.next_threshold()));
+                    ui.label(format!("Health {} / {}", hp.current, hp.max));
+                    if let Some(hunger) = e.try_get::<&Satiation>(|s| s.state()) {
+                        let color = if hunger >= HungerState::Hungry {
+                            graphic::egui::Color32::YELLOW
+                        } else {
+                            graphic::egui::Color32::LIGHT_GRAY
+                        };
+                        ui.colored_label(color, format!("You are {hunger}"));
+                    }
+                    if let Some((current, max)) = e.try_get::<&Mana>(|m| (m.current, m.max)) {
+                        ui.label(format!("Mana {current} / {max}"));
+                    }
+                    ui.label(format!("Accuracy {}", stats.accuracy));
+                    ui.label(format!("Evasion {}", stats.evasion));
+                    ui.label(format!("Crit chance {}", stats.crit_chance));
+                    ui.label(format!("Armor {}", armor_value(e)));
+                    ui.separator();
+                    for slot in EquipSlot::ALL {
+                        let item = slot
+                            .item(e)
+                            .and_then(|item| item.try_get::<&Item>(|i| i.name.clone()))
+                            .unwrap_or_else(|| "-".into());
+                        ui.label(format!("{slot:?}: {item}"));
+                    }
+                });
+            });
+
+        world
+            .system_named::<(&Statistics, &Kills, &AchievementList)>("EguiStatistics")
+            .term_singleton(0)
+            .term_singleton(1)
+            .term_singleton(2)
+            .with::<EguiEnabled>()
+            .singleton()
+            .each(|(stats, kills, list)| {
+                use graphic::egui::Color32;
+                graphic::egui::Window::new("Statistics")
+                    .default_open(false)
+                    .show(egui(), |ui| {
+                        for (label, tally) in [
+                            ("Damage dealt", &stats.damage_dealt),
+                            ("Damage taken", &stats.damage_taken),
+                        ] {
+                            let total: i32 = tally.values().sum();
+                            ui.label(format!("{label} {total}"));
+                            let mut kinds: Vec<_> = tally.iter().collect();
+                            kinds.sort_by_key(|(kind, _)| kind.to_string());
+                            for (kind, amount) in kinds {
+                                ui.label(format!("    {kind} {amount}"));
+                            }
+                        }
+                        ui.label(format!("Kills {}", kills.total()));
+                        for (name, count) in kills.sorted() {
+                            ui.label(format!("    {name} {count}"));
+                        }
+                        ui.label(format!("Tiles explored {}", stats.tiles_explored));
+                        ui.label(format!("Pushed into walls {}", stats.pushes_into_walls));
+                        ui.separator();
+                        ui.heading("Achievements");
+                        for achievement in &list.achievements {
+                            let (mark, color) = if stats.is_unlocked(achievement) {
+                                ("[x]", Color32::LIGHT_GREEN)
+                            } else {
+                                ("[ ]", Color32::GRAY)
+                            };
+                            ui.colored_label(color, format!("{mark} {}", achievement.name))
+                                .on_hover_text(&achievement.description);
+                        }
+                    });
+            });
+    }
+}
+
+fn severity_color(severity: Severity) -> graphic::egui::Color32 {
+    use graphic::egui::Color32;
+    match severity {
+        Severity::Info => Color32::LIGHT_GRAY,
+        Severity::Good => Color32::LIGHT_GREEN,
+        Severity::Warning => Color32::YELLOW,
+        Severity::Danger => Color32::LIGHT_RED,
+    }
+}
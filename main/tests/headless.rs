@@ -0,0 +1,109 @@
+use std::path::Path;
+use std::process::Command;
+
+use base::nanoserde::DeJson;
+use base::stats::Statistics;
+use base::util::pos::Direction;
+use flecsirogue::balance::{simulate, to_csv, RunReport};
+use flecsirogue::bot::Bot;
+use flecsirogue::headless::{Action, Simulation, DEFAULT_ASSETS};
+use flecsirogue::run::GameData;
+
+/// Walks in circles and bumps into whatever is in the way
+fn play(seed: u64, turns: u32) -> Simulation {
+    let sim = Simulation::new(Path::new(DEFAULT_ASSETS), seed).unwrap();
+    let dirs = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut i = 0;
+    while !sim.is_over() && sim.turn() < turns {
+        let (x, y) = dirs[i % dirs.len()];
+        if !sim.act(Action::Move(Direction { x, y })) {
+            sim.act(Action::Wait);
+        }
+        i += 1;
+    }
+    sim
+}
+
+#[test]
+fn turns_advance() {
+    let sim = Simulation::new(Path::new(DEFAULT_ASSETS), 3).unwrap();
+    assert_eq!(0, sim.turn());
+    assert!(sim.act(Action::Wait));
+    assert!(sim.act(Action::Wait));
+    assert_eq!(2, sim.turn());
+    // the run starts on the up stairs, which lead nowhere on the first level
+    assert!(!sim.act(Action::Ascend));
+    assert_eq!(2, sim.turn());
+}
+
+#[test]
+fn same_seed_same_run() {
+    let a = play(11, 60);
+    let b = play(11, 60);
+    assert_eq!(a.turn(), b.turn());
+    assert_eq!(a.player_pos(), b.player_pos());
+    assert_eq!(a.player_health().current, b.player_health().current);
+    assert_eq!(a.is_over(), b.is_over());
+}
+
+#[test]
+fn bot_explores() {
+    let sim = Simulation::new(Path::new(DEFAULT_ASSETS), 7).unwrap();
+    let explored = || sim.world.get::<&Statistics>(|s| s.tiles_explored);
+    let before = explored();
+    Bot::default().play(&sim, 100);
+    assert!(sim.is_over() || sim.turn() >= 100);
+    assert!(explored() > before);
+}
+
+#[test]
+fn balance_reports() {
+    let data = GameData::read(Path::new(DEFAULT_ASSETS)).unwrap();
+    let a = simulate(&data, &Bot::default(), 5, 2, 150).unwrap();
+    let b = simulate(&data, &Bot::default(), 5, 2, 150).unwrap();
+    assert_eq!(vec![5, 6], a.iter().map(|r| r.seed).collect::<Vec<_>>());
+    let csv = to_csv(&a);
+    assert_eq!(csv, to_csv(&b));
+    assert_eq!(3, csv.lines().count());
+    assert!(csv.starts_with("seed,survived,cause,depth,turns,kills,taken_cutting,"));
+}
+
+/// The report on stdout, nothing else may end up there
+fn balance_output(format: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_balance"))
+        .args([
+            "--seed", "5", "--runs", "2", "--turns", "150", "--format", format,
+        ])
+        .args(["--assets", DEFAULT_ASSETS])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn balance_output_parses() {
+    let data = GameData::read(Path::new(DEFAULT_ASSETS)).unwrap();
+    let reports = simulate(&data, &Bot::default(), 5, 2, 150).unwrap();
+
+    let json = balance_output("json");
+    assert_eq!(reports, Vec::<RunReport>::deserialize_json(&json).unwrap());
+
+    let csv = balance_output("csv");
+    assert_eq!(to_csv(&reports), csv);
+    let mut lines = csv.lines();
+    let columns = lines.next().unwrap().split(',').count();
+    for (line, report) in lines.zip(&reports) {
+        // the cause is quoted, everything else is a number or a bool
+        let (start, rest) = line.split_once(",\"").unwrap();
+        let (cause, rest) = rest.split_once("\",").unwrap();
+        let fields: Vec<&str> = start.split(',').chain(rest.split(',')).collect();
+        assert_eq!(columns - 1, fields.len());
+        assert_eq!(report.seed, fields[0].parse::<u64>().unwrap());
+        assert_eq!(report.survived, fields[1].parse::<bool>().unwrap());
+        assert_eq!(report.cause.as_deref().unwrap_or(""), cause);
+        assert_eq!(report.depth, fields[2].parse::<i32>().unwrap());
+        assert_eq!(report.turns, fields[3].parse::<u32>().unwrap());
+        assert_eq!(report.kills, fields[4].parse::<i32>().unwrap());
+    }
+}